@@ -1,3 +1,4 @@
+use std::env;
 use std::sync::{Arc, Mutex};
 
 use rcv::{AppState, MainWindow, NativeUiEx, Settings};
@@ -11,8 +12,16 @@ fn main() {
             // Store the app state in a ref-counted mutex in case we use threads later
             let app_state = Arc::new(Mutex::new(AppState::new(s)));
             // Build the main window
-            let _ui = MainWindow::build_ui(MainWindow::new(), app_state)
+            let ui = MainWindow::build_ui(MainWindow::new(), Arc::clone(&app_state))
                         .expect("Failed to create UI");
+            // Open a file named on the command line, "-" reads stdin e.g. `some_query | rusty_csv_viewer -`
+            // --lenient loads malformed rows without asking, which stdin needs as it can not be read again
+            let (options, args): (Vec<String>, Vec<String>) = env::args().skip(1).partition(|a| a == "--lenient");
+            if let Some(filename) = args.first() {
+                if let Err(e) = ui.cmd_open_path(&mut app_state.lock().unwrap(), filename, !options.is_empty()) {
+                    nwg::error_message("Open File", &e.to_string());
+                }
+            }
             // State the window message loop
             nwg::dispatch_thread_events();
         },
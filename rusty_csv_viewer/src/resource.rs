@@ -34,19 +34,30 @@ pub struct CDialogOpenFile {}
 
 impl CDialogOpenFile {
     pub const TITLE: &'static str = "Open a CSV file";
-    pub const FILTER: &'static str = "CSV(*.csv)|Text(*.txt)|All files(*.*)";
+    pub const FILTER: &'static str = "CSV(*.csv)|Text(*.txt)|Compressed(*.gz;*.zst;*.bz2;*.xz)|ZIP archives(*.zip)|JSON(*.json;*.jsonl;*.ndjson)|Spreadsheets(*.xlsx;*.xlsm;*.xlsb;*.xls;*.ods)|SQLite databases(*.sqlite;*.sqlite3;*.db;*.db3)|Columnar(*.parquet;*.arrow;*.feather)|HTML(*.html;*.htm)|Markdown(*.md;*.markdown)|All files(*.*)";
+    /// Files of at least this many bytes are memory mapped instead of read into memory
+    pub const MAP_SIZE: u64 = 256 * 1024 * 1024;
+}
+
+/// Structure that holds constants for the dialog that runs a query against a database
+pub struct CDialogQuery {}
+
+impl CDialogQuery {
+    pub const TITLE: &'static str = "Query a database";
+    pub const WINDOW_SIZE: (i32, i32) = (500, 300);
+    pub const RUN: &'static str = "&Run";
 }
 
 /// Structure that holds constants for the File Menu
 pub struct CMenuFile {}
 impl CMenuFile {
-    menu_label_const![ ["&File" | "&Open file", "&Close file", "E&xit"]@3 ];
+    menu_label_const![ ["&File" | "&Open file", "&Close file", "E&xit", "Cancel &loading", "&Query database..."]@5 ];
 }
 
 /// Structure that holds constants for the Edit Menu
 pub struct CMenuEdit {}
 impl CMenuEdit {
-    menu_label_const![ ["&Edit" | "&Find", "&Preferences"] @2 ];
+    menu_label_const![ ["&Edit" | "&Find", "&Preferences", "First row is &header", "View p&reamble"] @4 ];
 }
 
 /// Structure that holds constants for the Help Menu
@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+
+use flate2::bufread::DeflateDecoder;
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::table::dialect::Dialect;
+use crate::table::json::is_json_file;
+
+/// Separates the path of an archive from the name of a member inside it
+/// e.g. "bundle.zip!/data/prices.csv"
+pub const MEMBER_SEPARATOR: &str = "!/";
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Split a path into the archive path and the member name if it points inside an archive
+pub fn split_member_path(path: &str) -> (&str, Option<&str>) {
+    match path.find(MEMBER_SEPARATOR) {
+        Some(i) => (&path[..i], Some(&path[i + MEMBER_SEPARATOR.len()..])),
+        None => (path, None)
+    }
+}
+
+/// Join an archive path and a member name, see split_member_path
+pub fn member_path(archive: &str, member: &str) -> String {
+    format!("{}{}{}", archive, MEMBER_SEPARATOR, member)
+}
+
+/// Test whether a file is a ZIP archive from its magic bytes
+pub fn is_zip_file(filename: &str) -> bool {
+    let mut magic = [0; 4];
+
+    File::open(filename)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .map_or(false, |_| magic == ZIP_MAGIC)
+}
+
+/// List the members of a ZIP archive that look like delimited or JSON files by their extension
+///
+/// Directories and the resource forks macOS adds to archives are left out. Members are
+/// listed in the order they are stored
+pub fn list_members(filename: &str) -> io::Result<Vec<String>> {
+    let mut archive = ZipArchive::new(File::open(filename)?)?;
+    let mut members = Vec::new();
+
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        let name = file.name();
+
+        if !file.is_dir() && !name.starts_with("__MACOSX/")
+            && (Dialect::from_extension(name).is_some() || is_json_file(name)) {
+            members.push(name.to_string());
+        }
+    }
+
+    Ok(members)
+}
+
+/// Open a member of a ZIP archive without extracting it to disk
+///
+/// Stored and deflated members are read straight from the archive as they are read,
+/// without checking their CRC. Members compressed any other way are read into memory
+pub fn open_member(filename: &str, member: &str) -> io::Result<Box<dyn Read>> {
+    let mut archive = ZipArchive::new(File::open(filename)?)?;
+    let (method, start, length) = {
+        let file = archive.by_name(member)?;
+        (file.compression(), file.data_start(), file.compressed_size())
+    };
+
+    match method {
+        CompressionMethod::Stored | CompressionMethod::Deflated => {
+            let mut file = archive.into_inner();
+            file.seek(SeekFrom::Start(start))?;
+            let data = BufReader::new(file.take(length));
+
+            if method == CompressionMethod::Stored {
+                Ok(Box::new(data))
+            } else {
+                Ok(Box::new(DeflateDecoder::new(data)))
+            }
+        },
+        _ => {
+            let mut file = archive.by_name(member)?;
+            let mut data = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut data)?;
+
+            Ok(Box::new(Cursor::new(data)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    use super::*;
+    use crate::table::reader::{from_file, open_file};
+
+    fn setup_zip(target: &str, members: &[(&str, &str)]) {
+        let mut zip = ZipWriter::new(File::create(target).expect("create failed"));
+
+        for (name, data) in members {
+            zip.start_file(*name, FileOptions::default()).expect("zip write failed");
+            zip.write_all(data.as_bytes()).expect("zip write failed");
+        }
+
+        zip.finish().expect("zip write failed");
+    }
+
+    #[test]
+    fn test_split_member_path() {
+        assert_eq!(split_member_path("bundle.zip!/data/a.csv"), ("bundle.zip", Some("data/a.csv")));
+        assert_eq!(split_member_path("data/a.csv"), ("data/a.csv", None));
+        assert_eq!(member_path("bundle.zip", "data/a.csv"), "bundle.zip!/data/a.csv");
+    }
+
+    #[test]
+    fn test_zip_members() {
+        let f = "archive_members.zip";
+        setup_zip(f, &[ ("data/a.csv", "Name,Value\nvalue1,10\n"), ("readme.md", "hi"),
+                        ("__MACOSX/data/._a.csv", "xx"), ("b.tsv", "x\ty\n"), ("c.json", "[]") ]);
+
+        assert_eq!(is_zip_file(f), true);
+        assert_eq!(list_members(f).expect("list failed"), vec![ "data/a.csv".to_owned(), "b.tsv".to_owned(), "c.json".to_owned() ]);
+
+        let mut s = String::new();
+        open_member(f, "data/a.csv").expect("open failed").read_to_string(&mut s).expect("read error");
+        assert_eq!(s, "Name,Value\nvalue1,10\n");
+
+        assert!(open_member(f, "missing.csv").is_err());
+
+        // the member path reads straight from the archive
+        let d = Dialect { header: true, .. Dialect::csv() };
+        let r = from_file(&member_path(f, "data/a.csv"), &d).expect("read error").expect("parse error");
+        assert_eq!(r.data(), &vec![ "value1".to_owned(), "10".to_owned() ]);
+
+        assert_eq!(open_file(f).err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+
+        fs::remove_file(f).expect("remove failed");
+    }
+
+    #[test]
+    fn test_open_stored_member() {
+        let f = "archive_stored.zip";
+        let mut zip = ZipWriter::new(File::create(f).expect("create failed"));
+        zip.start_file("a.csv", FileOptions::default().compression_method(CompressionMethod::Stored))
+            .expect("zip write failed");
+        zip.write_all(b"a,b\n1,2\n").expect("zip write failed");
+        zip.start_file("b.csv", FileOptions::default()).expect("zip write failed");
+        zip.write_all(b"c,d\n").expect("zip write failed");
+        zip.finish().expect("zip write failed");
+
+        let mut s = String::new();
+        open_member(f, "a.csv").expect("open failed").read_to_string(&mut s).expect("read error");
+        fs::remove_file(f).expect("remove failed");
+
+        // the member ends where its data ends and not at the end of the archive
+        assert_eq!(s, "a,b\n1,2\n");
+    }
+
+    #[test]
+    fn test_is_zip_file() {
+        assert_eq!(is_zip_file("Cargo.toml"), false);
+        assert_eq!(is_zip_file("missing.zip"), false);
+    }
+}
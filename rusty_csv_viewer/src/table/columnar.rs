@@ -0,0 +1,461 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use std::fmt::Result as FmtResult;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::{root_as_footer, root_as_message};
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use parquet::arrow::arrow_reader::{ArrowReaderMetadata, ArrowReaderOptions, ParquetRecordBatchReaderBuilder};
+use parquet::errors::ParquetError;
+
+use crate::table::data::{ColumnSchema, Location, RecordSource, Table, TableDataValidationError};
+use crate::table::header::FieldType;
+
+/// Columnar file formats, which are read a chunk of rows at a time
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum ColumnarFormat {
+    /// Apache Parquet, read a row group at a time
+    Parquet,
+    /// Arrow IPC files, also known as Feather version 2, read a record batch at a time
+    Arrow,
+}
+
+impl ColumnarFormat {
+    pub const ALL: [ColumnarFormat; 2] = [ColumnarFormat::Parquet, ColumnarFormat::Arrow];
+
+    /// The bytes every file in this format starts with
+    pub fn magic(&self) -> &'static [u8] {
+        match self {
+            ColumnarFormat::Parquet => b"PAR1",
+            ColumnarFormat::Arrow => b"ARROW1",
+        }
+    }
+}
+
+impl Display for ColumnarFormat {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            ColumnarFormat::Parquet => write!(f, "Parquet"),
+            ColumnarFormat::Arrow => write!(f, "Arrow"),
+        }
+    }
+}
+
+/// Detect the format of a columnar file from its magic bytes. None means it is not columnar
+pub fn detect_columnar_file(filename: &str) -> Option<ColumnarFormat> {
+    let mut magic = Vec::with_capacity(6);
+
+    File::open(filename)
+        .and_then(|f| f.take(6).read_to_end(&mut magic))
+        .ok()?;
+
+    ColumnarFormat::ALL.iter()
+        .find(|f| magic.starts_with(f.magic()))
+        .copied()
+}
+
+/// Where the chunks of a columnar table are read from
+enum Source {
+    Parquet { file: File, metadata: ArrowReaderMetadata },
+    Arrow(RefCell<FileReader<File>>),
+}
+
+/// A table backed by a Parquet or Arrow IPC file
+///
+/// Opening the file only reads its schema and how many rows each chunk holds. A chunk is
+/// a row group of a Parquet file or a record batch of an Arrow file. Rows are read a chunk
+/// at a time when they are requested, and the last chunk read is kept for the rows around
+/// it, so files larger than the available memory can be viewed. Values are shown as Arrow
+/// formats them and the types of the columns are kept as the schema of the table.
+/// Chunks that can not be read are reported as errors of the table
+pub struct ColumnarTable {
+    source: Source,
+    format: ColumnarFormat,
+    header: Vec<String>,
+    schema: Vec<ColumnSchema>,
+    /// First row of every chunk, followed by the number of rows
+    starts: Vec<usize>,
+    /// The chunk read last and its record batches
+    cache: RefCell<Option<(usize, Vec<RecordBatch>)>>,
+    /// Chunks that could not be read
+    errors: RefCell<Vec<TableDataValidationError>>,
+}
+
+impl ColumnarTable {
+    /// Open a Parquet or Arrow IPC file, detected by its magic bytes
+    ///
+    /// Fails with InvalidData if the file is in neither format or is damaged
+    pub fn open(filename: &str) -> io::Result<Self> {
+        match detect_columnar_file(filename) {
+            Some(ColumnarFormat::Parquet) => ColumnarTable::open_parquet(filename),
+            Some(ColumnarFormat::Arrow) => ColumnarTable::open_arrow(filename),
+            None => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                       format!("{} is not a Parquet or Arrow file", filename)))
+        }
+    }
+
+    fn open_parquet(filename: &str) -> io::Result<Self> {
+        let file = File::open(filename)?;
+        let metadata = ArrowReaderMetadata::load(&file, ArrowReaderOptions::new()).map_err(parquet_error)?;
+
+        let counts = metadata.metadata().row_groups().iter()
+            .map(|g| g.num_rows() as usize)
+            .collect::<Vec<_>>();
+        let schema = metadata.schema().clone();
+
+        Ok(ColumnarTable::new(Source::Parquet { file, metadata }, ColumnarFormat::Parquet, &schema, &counts))
+    }
+
+    fn open_arrow(filename: &str) -> io::Result<Self> {
+        let reader = FileReader::try_new(File::open(filename)?, None).map_err(arrow_error)?;
+        let schema = reader.schema();
+        let counts = count_arrow_rows(&mut File::open(filename)?)?;
+
+        Ok(ColumnarTable::new(Source::Arrow(RefCell::new(reader)), ColumnarFormat::Arrow, &schema, &counts))
+    }
+
+    fn new(source: Source, format: ColumnarFormat, schema: &Schema, counts: &[usize]) -> Self {
+        let schema = schema.fields().iter().map(|f| column_schema(f)).collect::<Vec<_>>();
+        let header = schema.iter().map(|c| c.name.clone()).collect();
+
+        let mut starts = vec![0];
+        for count in counts {
+            starts.push(starts[starts.len() - 1] + count);
+        }
+
+        ColumnarTable {
+            source, format, header, schema, starts, cache: RefCell::new(None), errors: RefCell::new(Vec::new())
+        }
+    }
+
+    pub fn format(&self) -> ColumnarFormat {
+        self.format
+    }
+
+    /// Number of row groups or record batches in the file
+    pub fn chunks(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    /// Read the record batches of a chunk
+    fn read_chunk(&self, chunk: usize) -> io::Result<Vec<RecordBatch>> {
+        match &self.source {
+            Source::Parquet { file, metadata } => {
+                ParquetRecordBatchReaderBuilder::new_with_metadata(file.try_clone()?, metadata.clone())
+                    .with_row_groups(vec![chunk])
+                    .build()
+                    .map_err(parquet_error)?
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(arrow_error)
+            },
+            Source::Arrow(reader) => {
+                let mut reader = reader.borrow_mut();
+                reader.set_index(chunk).map_err(arrow_error)?;
+
+                reader.next()
+                    .transpose()
+                    .map(|batch| batch.into_iter().collect())
+                    .map_err(arrow_error)
+            }
+        }
+    }
+
+    /// Add a chunk that could not be read to the errors unless it is there already
+    fn report(&self, chunk: usize, error: io::Error) {
+        let row = self.starts[chunk] + 1;
+        let mut errors = self.errors.borrow_mut();
+
+        if errors.iter().all(|e| e.position().0 != row) {
+            errors.push(TableDataValidationError::ReadError {
+                row, kind: error.kind(), location: Location::default(),
+                message: format!("rows {} to {} could not be read: {}", row, self.starts[chunk + 1], error)
+            });
+        }
+    }
+}
+
+impl Table for ColumnarTable {
+    fn header(&self) -> &[String] {
+        &self.header
+    }
+
+    fn columns(&self) -> usize {
+        self.header.len()
+    }
+
+    fn rows(&self) -> usize {
+        self.starts[self.starts.len() - 1]
+    }
+
+    /// The fields of a row, or None if it is out of range or its chunk can not be read,
+    /// which is added to the errors once
+    fn row(&self, row: usize) -> Option<Vec<String>> {
+        if row >= self.rows() {
+            return None;
+        }
+
+        let chunk = self.starts.partition_point(|s| *s <= row) - 1;
+        let mut cache = self.cache.borrow_mut();
+
+        if cache.as_ref().map_or(true, |(c, _)| *c != chunk) {
+            match self.read_chunk(chunk) {
+                Ok(batches) => *cache = Some((chunk, batches)),
+                Err(e) => {
+                    self.report(chunk, e);
+                    return None;
+                }
+            }
+        }
+
+        let (_, batches) = cache.as_ref()?;
+        let mut index = row - self.starts[chunk];
+
+        for batch in batches {
+            if index < batch.num_rows() {
+                return format_row(batch, index).ok();
+            }
+
+            index -= batch.num_rows();
+        }
+
+        None
+    }
+
+    /// Columnar files have no lines or offsets for rows to start at
+    fn source(&self, _row: usize) -> Option<RecordSource> {
+        None
+    }
+
+    fn errors(&self) -> Cow<'_, [TableDataValidationError]> {
+        Cow::Owned(self.errors.borrow().clone())
+    }
+
+    fn schema(&self) -> Option<&[ColumnSchema]> {
+        Some(&self.schema)
+    }
+}
+
+/// Count the rows of every record batch of an Arrow IPC file
+///
+/// The footer only says where each batch is, so the row count is taken from the metadata
+/// at the start of every batch without reading its columns
+fn count_arrow_rows(file: &mut File) -> io::Result<Vec<usize>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    // the file ends with the length of the footer and the magic bytes
+    let mut trailer = [0; 10];
+    file.seek(SeekFrom::End(-10))?;
+    file.read_exact(&mut trailer)?;
+
+    if &trailer[4..] != ColumnarFormat::Arrow.magic() {
+        return Err(invalid("the Arrow file has no footer".to_owned()));
+    }
+
+    let length = i32::from_le_bytes([ trailer[0], trailer[1], trailer[2], trailer[3] ]);
+    let mut footer = vec![0; length.max(0) as usize];
+    file.seek(SeekFrom::End(-10 - footer.len() as i64))?;
+    file.read_exact(&mut footer)?;
+
+    let footer = root_as_footer(&footer).map_err(|e| invalid(e.to_string()))?;
+    let blocks = footer.recordBatches().map(|b| b.iter().collect::<Vec<_>>()).unwrap_or_default();
+    let mut counts = Vec::with_capacity(blocks.len());
+
+    for block in blocks {
+        let mut metadata = vec![0; block.metaDataLength().max(0) as usize];
+        file.seek(SeekFrom::Start(block.offset() as u64))?;
+        file.read_exact(&mut metadata)?;
+
+        // the length of the message follows a continuation marker in newer files
+        let start = if metadata.starts_with(&[0xff; 4]) { 8 } else { 4 };
+        let message = root_as_message(metadata.get(start..).unwrap_or_default())
+            .map_err(|e| invalid(e.to_string()))?;
+        let batch = message.header_as_record_batch()
+            .ok_or_else(|| invalid("a block of the Arrow file is not a record batch".to_owned()))?;
+
+        counts.push(batch.length() as usize);
+    }
+
+    Ok(counts)
+}
+
+/// Format the values of a row of a record batch. Nulls are empty
+fn format_row(batch: &RecordBatch, index: usize) -> Result<Vec<String>, ArrowError> {
+    let options = FormatOptions::default();
+
+    batch.columns().iter()
+        .map(|c| ArrayFormatter::try_new(c.as_ref(), &options).map(|f| f.value(index).to_string()))
+        .collect()
+}
+
+fn column_schema(field: &Field) -> ColumnSchema {
+    ColumnSchema {
+        name: field.name().clone(),
+        field_type: field_type(field.data_type()),
+        source_type: field.data_type().to_string(),
+        nullable: field.is_nullable(),
+    }
+}
+
+/// The kind of values of an Arrow type. Nested and binary types are shown as text
+fn field_type(data_type: &DataType) -> FieldType {
+    match data_type {
+        DataType::Null => FieldType::Empty,
+        DataType::Boolean => FieldType::Boolean,
+        DataType::Dictionary(_, values) => field_type(values),
+        t if t.is_numeric() => FieldType::Number,
+        t if t.is_temporal() => FieldType::Date,
+        _ => FieldType::Text,
+    }
+}
+
+fn arrow_error(e: ArrowError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn parquet_error(e: ParquetError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray};
+    use arrow::ipc::writer::FileWriter;
+    use parquet::arrow::ArrowWriter;
+    use parquet::file::properties::WriterProperties;
+
+    use super::*;
+
+    macro_rules! make_strvec {
+        [ $($a:expr),+ ] => {
+            vec![ $($a.to_owned()),+ ]
+        }
+    }
+
+    fn batches() -> (Arc<Schema>, Vec<RecordBatch>) {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("value", DataType::Float64, true),
+            Field::new("stock", DataType::Int64, false),
+        ]));
+
+        let batch = |names: Vec<&str>, values: Vec<Option<f64>>, stock: Vec<i64>| {
+            let columns: Vec<ArrayRef> = vec![
+                Arc::new(StringArray::from(names)),
+                Arc::new(Float64Array::from(values)),
+                Arc::new(Int64Array::from(stock)),
+            ];
+
+            RecordBatch::try_new(Arc::clone(&schema), columns).expect("invalid batch")
+        };
+
+        let batches = vec![
+            batch(vec![ "value1", "value2" ], vec![ Some(10.5), None ], vec![ 3, -1 ]),
+            batch(vec![ "value3" ], vec![ Some(2.0) ], vec![ 7 ]),
+        ];
+
+        (schema, batches)
+    }
+
+    fn check_table(table: &ColumnarTable) {
+        assert_eq!(table.header(), &make_strvec![ "name", "value", "stock" ][..]);
+        assert_eq!(table.rows(), 3);
+        assert_eq!(table.chunks(), 2);
+
+        // rows are read from either chunk in any order
+        assert_eq!(table.row(2), Some(make_strvec![ "value3", "2.0", "7" ]));
+        assert_eq!(table.row(1), Some(make_strvec![ "value2", "", "-1" ]));
+        assert_eq!(table.row(3), None);
+
+        let schema = table.schema().expect("no schema");
+        assert_eq!(schema[1], ColumnSchema { name: "value".to_owned(), field_type: FieldType::Number,
+                                             source_type: "Float64".to_owned(), nullable: true });
+        assert_eq!(schema[0].field_type, FieldType::Text);
+    }
+
+    #[test]
+    fn test_parquet_table() {
+        let f = "columnar_table.parquet";
+        let (schema, batches) = batches();
+
+        // one row group per batch
+        let properties = WriterProperties::builder().set_max_row_group_size(2).build();
+        let mut writer = ArrowWriter::try_new(File::create(f).expect("create failed"), schema, Some(properties))
+            .expect("write failed");
+
+        for batch in &batches {
+            writer.write(batch).expect("write failed");
+        }
+
+        writer.close().expect("write failed");
+
+        assert_eq!(detect_columnar_file(f), Some(ColumnarFormat::Parquet));
+        let table = ColumnarTable::open(f).expect("open failed");
+        fs::remove_file(f).expect("remove failed");
+
+        assert_eq!(table.format(), ColumnarFormat::Parquet);
+        check_table(&table);
+    }
+
+    #[test]
+    fn test_arrow_table() {
+        let f = "columnar_table.arrow";
+        let (schema, batches) = batches();
+
+        let mut writer = FileWriter::try_new(File::create(f).expect("create failed"), &schema).expect("write failed");
+
+        for batch in &batches {
+            writer.write(batch).expect("write failed");
+        }
+
+        writer.finish().expect("write failed");
+
+        assert_eq!(detect_columnar_file(f), Some(ColumnarFormat::Arrow));
+        let table = ColumnarTable::open(f).expect("open failed");
+        fs::remove_file(f).expect("remove failed");
+
+        assert_eq!(table.format(), ColumnarFormat::Arrow);
+        check_table(&table);
+    }
+
+    #[test]
+    fn test_chunk_read_error() {
+        let f = "columnar_truncated.arrow";
+        let (schema, batches) = batches();
+
+        let mut writer = FileWriter::try_new(File::create(f).expect("create failed"), &schema).expect("write failed");
+
+        for batch in &batches {
+            writer.write(batch).expect("write failed");
+        }
+
+        writer.finish().expect("write failed");
+
+        let table = ColumnarTable::open(f).expect("open failed");
+        OpenOptions::new().write(true).open(f).and_then(|f| f.set_len(0)).expect("truncate failed");
+
+        // every chunk that can not be read is reported once
+        assert_eq!(table.window(0..3), Vec::<Vec<String>>::new());
+        assert_eq!(table.row(0), None);
+        fs::remove_file(f).expect("remove failed");
+
+        let errors = table.errors();
+        assert_eq!(errors.iter().map(|e| e.position()).collect::<Vec<_>>(), vec![ (1, 1), (3, 1) ]);
+        assert!(matches!(&errors[0], TableDataValidationError::ReadError { message, .. }
+                         if message.starts_with("rows 1 to 2 could not be read")));
+    }
+
+    #[test]
+    fn test_not_columnar() {
+        assert_eq!(detect_columnar_file("Cargo.toml"), None);
+        assert_eq!(ColumnarTable::open("Cargo.toml").err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt::{Display, Formatter};
+use std::fmt::Result as FmtResult;
+use std::io::{self, BufReader, Cursor, Read};
+
+/// Number of bytes read from the start of the input to detect the compression
+const MAGIC_SIZE: usize = 6;
+
+/// Compression formats that are recognised by the magic bytes at the start of the input
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    pub const ALL: [Compression; 4] = [Compression::Gzip, Compression::Zstd, Compression::Bzip2, Compression::Xz];
+
+    /// The bytes every compressed stream in this format starts with
+    pub fn magic(&self) -> &'static [u8] {
+        match self {
+            Compression::Gzip => &[0x1F, 0x8B],
+            Compression::Zstd => &[0x28, 0xB5, 0x2F, 0xFD],
+            Compression::Bzip2 => b"BZh",
+            Compression::Xz => &[0xFD, b'7', b'z', b'X', b'Z', 0x00],
+        }
+    }
+
+    /// The extension usually added to the name of a compressed file
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+            Compression::Bzip2 => ".bz2",
+            Compression::Xz => ".xz",
+        }
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Zstd => write!(f, "zstd"),
+            Compression::Bzip2 => write!(f, "bzip2"),
+            Compression::Xz => write!(f, "xz"),
+        }
+    }
+}
+
+/// Detect the compression of the input from its first bytes. None means it is not compressed
+pub fn detect_compression(sample: &[u8]) -> Option<Compression> {
+    Compression::ALL.iter()
+        .find(|c| sample.starts_with(c.magic()))
+        .copied()
+}
+
+/// Strip the extension of a compression format from a file name
+///
+/// "data.csv.gz" becomes "data.csv" so the dialect can still be picked from the extension
+pub fn strip_compression_extension(filename: &str) -> &str {
+    Compression::ALL.iter()
+        .find_map(|c| filename.strip_suffix(c.extension()))
+        .unwrap_or(filename)
+}
+
+/// Reader that decompresses its input
+///
+/// The compression is detected from the magic bytes at the start of the input. Input
+/// that is not compressed is passed through unchanged. Streams of several concatenated
+/// members, as written by parallel compressors, are read in full.
+pub struct DecompressReader<'a> {
+    reader: Box<dyn Read + 'a>,
+    compression: Option<Compression>,
+}
+
+impl<'a> DecompressReader<'a> {
+    pub fn new<R: Read + 'a>(mut reader: R) -> io::Result<Self> {
+        let mut magic = Vec::with_capacity(MAGIC_SIZE);
+        reader.by_ref().take(MAGIC_SIZE as u64).read_to_end(&mut magic)?;
+
+        let compression = detect_compression(&magic);
+        // the decoders read the magic bytes again
+        let input = BufReader::new(Cursor::new(magic).chain(reader));
+
+        let reader: Box<dyn Read + 'a> = match compression {
+            None => Box::new(input),
+            Some(Compression::Gzip) => Box::new(flate2::bufread::MultiGzDecoder::new(input)),
+            Some(Compression::Zstd) => Box::new(zstd::stream::read::Decoder::with_buffer(input)?),
+            Some(Compression::Bzip2) => Box::new(bzip2::bufread::MultiBzDecoder::new(input)),
+            Some(Compression::Xz) => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(input)),
+        };
+
+        Ok(DecompressReader { reader, compression })
+    }
+
+    /// The compression of the input or None if it is not compressed
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+}
+
+impl<'a> Read for DecompressReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "Name,Value\nvalue1,10\n" in a stored deflate block so the data stays readable
+    const GZIP: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x04\x03\x01\x15\x00\xea\xff\
+                          Name,Value\nvalue1,10\n\x59\x7e\x39\x46\x15\x00\x00\x00";
+
+    fn decompress_all(input: &[u8]) -> (Option<Compression>, String) {
+        let mut r = DecompressReader::new(input).expect("read error");
+        let mut s = String::new();
+        r.read_to_string(&mut s).expect("read error");
+
+        (r.compression(), s)
+    }
+
+    #[test]
+    fn test_detect_compression() {
+        assert_eq!(detect_compression(b"\x1f\x8b\x08\x00"), Some(Compression::Gzip));
+        assert_eq!(detect_compression(b"\x28\xb5\x2f\xfd\x24"), Some(Compression::Zstd));
+        assert_eq!(detect_compression(b"BZh91AY&SY"), Some(Compression::Bzip2));
+        assert_eq!(detect_compression(b"\xfd7zXZ\x00\x00"), Some(Compression::Xz));
+        assert_eq!(detect_compression(b"Name,Value\n"), None);
+        assert_eq!(detect_compression(b"\x1f"), None);
+    }
+
+    #[test]
+    fn test_decompress_gzip() {
+        assert_eq!(decompress_all(GZIP), (Some(Compression::Gzip), "Name,Value\nvalue1,10\n".to_owned()));
+
+        // concatenated members are read one after the other
+        let twice = [GZIP, GZIP].concat();
+        assert_eq!(decompress_all(&twice).1, "Name,Value\nvalue1,10\n".repeat(2));
+    }
+
+    #[test]
+    fn test_decompress_passes_through() {
+        assert_eq!(decompress_all(b"a,b\nc,d\n"), (None, "a,b\nc,d\n".to_owned()));
+        // input shorter than the longest magic
+        assert_eq!(decompress_all(b"a"), (None, "a".to_owned()));
+        assert_eq!(decompress_all(b""), (None, String::new()));
+    }
+
+    #[test]
+    fn test_strip_compression_extension() {
+        assert_eq!(strip_compression_extension("data.csv.gz"), "data.csv");
+        assert_eq!(strip_compression_extension("data.tsv.zst"), "data.tsv");
+        assert_eq!(strip_compression_extension("data.csv"), "data.csv");
+    }
+}
@@ -1,13 +1,121 @@
+use std::borrow::Cow;
 use std::cmp;
 use std::error;
 use std::fmt::{Display, Formatter};
 use std::fmt::Result as FmtResult;
+use std::io;
+use std::ops::Range;
+
+use crate::table::dialect::{LineEnding, LineEndings};
+use crate::table::header::FieldType;
+
+/// Read access to a table regardless of how its rows are stored
+///
+/// Rows are numbered from 0 and do not include the header
+pub trait Table: Send {
+    fn header(&self) -> &[String];
+
+    fn columns(&self) -> usize;
+
+    fn rows(&self) -> usize;
+
+    /// The fields of a row or None if it is out of range
+    fn row(&self, row: usize) -> Option<Vec<String>>;
+
+    /// The rows in a range such as the rows visible in a window
+    fn window(&self, rows: Range<usize>) -> Vec<Vec<String>> {
+        rows.filter_map(|r| self.row(r)).collect()
+    }
+
+    /// Where a row starts in the source
+    fn source(&self, row: usize) -> Option<RecordSource>;
+
+    /// Problems that were skipped over when the table was read, including rows of a
+    /// lazily read table that could not be read when they were asked for
+    fn errors(&self) -> Cow<'_, [TableDataValidationError]> {
+        Cow::Borrowed(&[])
+    }
+
+    /// Lines skipped at the start of the source, see Dialect::skip_lines
+    fn preamble(&self) -> &[String] {
+        &[]
+    }
+
+    /// Names and types of the columns if the source has a schema
+    fn schema(&self) -> Option<&[ColumnSchema]> {
+        None
+    }
+}
+
+/// A column of a source that stores its values with a type, such as a Parquet file
+#[derive(Debug,Clone,PartialEq)]
+pub struct ColumnSchema {
+    pub name: String,
+    /// Kind of the values, as would be inferred from their text
+    pub field_type: FieldType,
+    /// Type as the source names it e.g. "Int64" or "Timestamp(Millisecond, None)"
+    pub source_type: String,
+    pub nullable: bool,
+}
 
 #[derive(Debug)]
 pub struct TableData {
     pub header: Vec<String>,
     pub data: Vec<String>,
     pub dims: (usize, usize),
+    /// Line endings of the source so a writer can reproduce them
+    pub line_endings: LineEndings,
+    /// Problems that were skipped over when the table was read leniently
+    pub errors: Vec<TableDataValidationError>,
+    /// Where each data row starts in the source
+    pub sources: Vec<RecordSource>,
+    /// Lines skipped before the first record without their line endings
+    pub preamble: Vec<String>,
+}
+
+impl Table for TableData {
+    fn header(&self) -> &[String] {
+        &self.header
+    }
+
+    fn columns(&self) -> usize {
+        self.dims.0
+    }
+
+    fn rows(&self) -> usize {
+        self.dims.1
+    }
+
+    fn row(&self, row: usize) -> Option<Vec<String>> {
+        let cols = self.columns();
+
+        if row < self.rows() {
+            Some(self.data[row*cols..(row+1)*cols].to_vec())
+        } else {
+            None
+        }
+    }
+
+    fn source(&self, row: usize) -> Option<RecordSource> {
+        self.sources.get(row).copied()
+    }
+
+    fn errors(&self) -> Cow<'_, [TableDataValidationError]> {
+        Cow::Borrowed(&self.errors)
+    }
+
+    fn preamble(&self) -> &[String] {
+        &self.preamble
+    }
+}
+
+/// Where a record starts in the source
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub struct RecordSource {
+    /// 1-based physical line
+    pub line: usize,
+    /// Byte offset from the start of the decoded input
+    pub offset: usize,
 }
 
 impl TableData {
@@ -16,6 +124,10 @@ impl TableData {
             header: Vec::new(),
             data: Vec::new(),
             dims: (0, 0),
+            line_endings: LineEndings::default(),
+            errors: Vec::new(),
+            sources: Vec::new(),
+            preamble: Vec::new(),
         }
     }
 
@@ -48,6 +160,32 @@ impl TableData {
         panic!("TableData: column mismatch when attempting to update the data field")
     }
 
+    /// Add empty columns up to the given count to the header and every row
+    pub fn widen(&mut self, cols: usize) {
+        let (old_cols, rows) = self.dims;
+
+        if cols <= old_cols {
+            return;
+        }
+
+        if self.has_headers() {
+            self.header.resize(cols, String::new());
+        }
+
+        if old_cols > 0 {
+            let mut data = Vec::with_capacity(cols * rows);
+
+            for row in self.data.chunks(old_cols) {
+                data.extend_from_slice(row);
+                data.resize(data.len() + cols - old_cols, String::new());
+            }
+
+            self.data = data;
+        }
+
+        self.set_dims(cols, rows);
+    }
+
     pub fn has_headers(&self) -> bool {
         self.header.len() > 0
     }
@@ -75,11 +213,29 @@ impl TableData {
     pub fn data(&self) -> &Vec<String> {
         &self.data
     }
+
+    pub fn line_ending(&self) -> Option<LineEnding> {
+        self.line_endings.style()
+    }
+
+    pub fn errors(&self) -> &Vec<TableDataValidationError> {
+        &self.errors
+    }
+
+    pub fn preamble(&self) -> &Vec<String> {
+        &self.preamble
+    }
+
+    /// Where a data row starts in the source or None if it is out of range
+    /// or the table was not read from a source
+    pub fn source(&self, row: usize) -> Option<RecordSource> {
+        self.sources.get(row).copied()
+    }
 }
 
 /// Csv validation error sub-types
 /// InvalidEscapeError
-#[derive(Debug,PartialEq)]
+#[derive(Debug,Clone,PartialEq)]
 pub enum QuoteValidationError {
     InvalidEscapeError,
     InvalidQuoteError,
@@ -101,40 +257,119 @@ impl Display for QuoteValidationError {
     }
 }
 
+/// Where in the source an error was found
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct Location {
+    /// 1-based physical line. Every '\n', '\r\n' or '\r' starts a new line
+    pub line: usize,
+    /// Byte offset from the start of the decoded input
+    pub offset: usize,
+    /// 1-based character column of the failing character within its line
+    pub column: usize,
+    /// Raw text around the failing character with a caret marker on the line below
+    pub snippet: String,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "line {}, column {}, byte {}", self.line, self.column, self.offset)
+    }
+}
+
 /// Primary csv validation error types
-#[derive(Debug,PartialEq)]
+///
+/// The row is the logical record number which excludes the header and counts a
+/// multi-line record once. The location points at the physical source.
+#[derive(Debug,Clone,PartialEq)]
 pub enum TableDataValidationError {
     QuoteValidationError {
         subtype: QuoteValidationError,
-        row: i32,
-        col: i32,
-        value: String
+        row: usize,
+        col: usize,
+        value: String,
+        location: Location
     },
     RowFieldCountMismatchError {
-        row: i32,
+        row: usize,
         expected:
         usize,
-        found: usize
+        found: usize,
+        location: Location
+    },
+    /// The underlying reader failed part way through the input
+    ReadError {
+        row: usize,
+        kind: io::ErrorKind,
+        message: String,
+        location: Location
+    },
+    /// A record of JSON input is not valid JSON or not an object
+    JsonError {
+        row: usize,
+        message: String,
+        location: Location
     },
 }
 
+/// Longest part of a field value shown in an error message
+const MAX_VALUE_CHARS: usize = 64;
+
 
 /// Display trait for displaying Validation error messages
 impl Display for TableDataValidationError {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         match self {
             TableDataValidationError::QuoteValidationError {
-                subtype, row, col, value } =>
+                subtype, row, col, value, location } =>
                 {
-                    let max_value_len = cmp::min(64, value.len());
-                    write!(f, "At row {}. {} in column: {}, value: {}",
-                           row, subtype, col, &value[0..max_value_len])
+                    let value = value.chars().take(MAX_VALUE_CHARS).collect::<String>();
+                    write!(f, "At row {} ({}). {} in column: {}, value: {}",
+                           row, location, subtype, col, value)?
                 }
 
             TableDataValidationError::RowFieldCountMismatchError {
-                row, expected, found } =>
-                write!(f, "At row {}. Field count mismatch. Expected: {}, Found: {}",
-                       row, expected, found),
+                row, expected, found, location } =>
+                write!(f, "At row {} ({}). Field count mismatch. Expected: {}, Found: {}",
+                       row, location, expected, found)?,
+
+            TableDataValidationError::ReadError {
+                row, message, location, .. } =>
+                write!(f, "At row {} ({}). Read error: {}", row, location, message)?,
+
+            TableDataValidationError::JsonError {
+                row, message, location } =>
+                write!(f, "At row {} ({}). JSON error: {}", row, location, message)?,
+        }
+
+        if !self.location().snippet.is_empty() {
+            write!(f, "\n{}", self.location().snippet)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TableDataValidationError {
+    /// The row and column the error was found at
+    ///
+    /// A field count mismatch points at the first missing or extra column
+    pub fn position(&self) -> (usize, usize) {
+        match self {
+            TableDataValidationError::QuoteValidationError { row, col, .. } => (*row, *col),
+            TableDataValidationError::RowFieldCountMismatchError { row, expected, found, .. } =>
+                (*row, cmp::min(*expected, *found) + 1),
+            TableDataValidationError::ReadError { row, .. } |
+            TableDataValidationError::JsonError { row, .. } => (*row, 1),
+        }
+    }
+
+    /// Where in the source the error was found
+    pub fn location(&self) -> &Location {
+        match self {
+            TableDataValidationError::QuoteValidationError { location, .. } |
+            TableDataValidationError::RowFieldCountMismatchError { location, .. } |
+            TableDataValidationError::ReadError { location, .. } |
+            TableDataValidationError::JsonError { location, .. } => location,
         }
     }
 }
@@ -192,4 +427,38 @@ mod tests {
         assert_eq!(c.has_headers(), true);
         assert_eq!(c.has_data(), true);
     }
+
+    #[test]
+    fn test_table_data_widen() {
+        let mut hdr = make_strvec![ "Name", "Type" ];
+        let mut data = make_strvec![ "a", "b", "c", "d" ];
+        let mut c = TableData::new();
+
+        c.set_header(&mut hdr);
+        c.set_data(&mut data, 2);
+        c.widen(3);
+
+        assert_eq!(c.columns(), 3);
+        assert_eq!(c.rows(), 2);
+        assert_eq!(c.header(), &make_strvec![ "Name", "Type", "" ]);
+        assert_eq!(c.data(), &make_strvec![ "a", "b", "", "c", "d", "" ]);
+    }
+
+    #[test]
+    fn test_table_data_as_table() {
+        let mut hdr = make_strvec![ "Name", "Type" ];
+        let mut data = make_strvec![ "a", "b", "c", "d", "e", "f" ];
+        let mut c = TableData::new();
+
+        c.set_header(&mut hdr);
+        c.set_data(&mut data, 2);
+
+        let t: &dyn Table = &c;
+
+        assert_eq!(t.header(), &make_strvec![ "Name", "Type" ][..]);
+        assert_eq!(t.rows(), 3);
+        assert_eq!(t.row(1), Some(make_strvec![ "c", "d" ]));
+        assert_eq!(t.row(3), None);
+        assert_eq!(t.window(1..5), vec![ make_strvec![ "c", "d" ], make_strvec![ "e", "f" ] ]);
+    }
 }
\ No newline at end of file
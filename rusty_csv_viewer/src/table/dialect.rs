@@ -0,0 +1,217 @@
+use std::io;
+
+use crate::table::compression::strip_compression_extension;
+
+/// Describes the format of a delimited text file
+#[derive(Debug,Clone,PartialEq)]
+pub struct Dialect {
+    /// Separates the fields of a record when the separator is `Separator::Delimiter`
+    pub delimiter: char,
+    /// How the fields of a record are separated
+    pub separator: Separator,
+    /// Surrounds fields that contain delimiters, quotes or terminators
+    pub quote: char,
+    /// Makes the next character literal e.g. a backslash. Doubled quotes are always accepted
+    pub escape: Option<char>,
+    /// Lines that start with this prefix are skipped
+    pub comment: Option<String>,
+    /// Number of lines at the start of the input skipped as a preamble e.g. the metadata
+    /// lines at the top of an instrument or bank export
+    pub skip_lines: usize,
+    /// After skip_lines, lines are skipped as a preamble until one matches this regular
+    /// expression. The matching line is the first record
+    pub skip_until: Option<String>,
+    /// Which fields have surrounding whitespace removed
+    pub trim: Trim,
+    /// The first record is a header
+    pub header: bool,
+    /// What ends a record
+    pub terminator: Terminator,
+    /// Convert '\r\n' inside quoted fields to '\n' instead of keeping it as is
+    pub normalize_newlines: bool,
+    /// Load malformed input instead of stopping at the first error. Short rows are padded,
+    /// long rows add overflow columns and stray quotes are taken literally
+    pub lenient: bool,
+}
+
+/// How the fields of a record are separated
+#[derive(Debug,Clone,PartialEq)]
+pub enum Separator {
+    /// The delimiter character of the dialect
+    Delimiter,
+    /// One or more spaces or tabs as in the output of `ps` or `df`. Whitespace at the start
+    /// and end of a line is ignored
+    Whitespace,
+    /// A string of one or more characters such as "||" or "::"
+    Text(String),
+    /// Text matching a regular expression. Quotes and escapes are taken literally as the
+    /// expression can match them
+    Regex(String),
+}
+
+/// Whitespace trimming policy for unquoted whitespace around a field
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Trim {
+    None,
+    Headers,
+    Fields,
+    All,
+}
+
+/// Line terminator policy
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Terminator {
+    /// Records end in a '\n', '\r\n' or '\r' in any mix
+    Default,
+    /// Records end in the given character. Line breaks are kept as field data
+    Char(char),
+}
+
+/// Style of the line endings found in the input
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+    /// More than one style was found
+    Mixed,
+}
+
+/// Number of each style of line ending outside of quoted fields
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub struct LineEndings {
+    pub lf: usize,
+    pub crlf: usize,
+    pub cr: usize,
+}
+
+impl LineEndings {
+    /// The style of the line endings or None if there were none
+    pub fn style(&self) -> Option<LineEnding> {
+        match (self.lf > 0, self.crlf > 0, self.cr > 0) {
+            (false, false, false) => None,
+            (true, false, false) => Some(LineEnding::Lf),
+            (false, true, false) => Some(LineEnding::CrLf),
+            (false, false, true) => Some(LineEnding::Cr),
+            _ => Some(LineEnding::Mixed),
+        }
+    }
+
+    /// The most common line ending which a writer can use for mixed input
+    /// Ties prefer '\r\n', then '\n'
+    pub fn dominant(&self) -> Option<&'static str> {
+        let counts = [(self.crlf, "\r\n"), (self.lf, "\n"), (self.cr, "\r")];
+
+        counts.iter()
+            .filter(|(n, _)| *n > 0)
+            .fold(None, |best: Option<(usize, &'static str)>, (n, s)| match best {
+                Some((b, _)) if b >= *n => best,
+                _ => Some((*n, *s))
+            })
+            .map(|(_, s)| s)
+    }
+}
+
+impl Dialect {
+    /// Comma separated values with double quotes
+    pub fn csv() -> Self {
+        Dialect {
+            delimiter: ',',
+            separator: Separator::Delimiter,
+            quote: '"',
+            escape: None,
+            comment: None,
+            skip_lines: 0,
+            skip_until: None,
+            trim: Trim::None,
+            header: false,
+            terminator: Terminator::Default,
+            normalize_newlines: false,
+            lenient: false,
+        }
+    }
+
+    /// Tab separated values with double quotes
+    pub fn tsv() -> Self {
+        Dialect {
+            delimiter: '\t',
+            .. Dialect::csv()
+        }
+    }
+
+    /// Pick a dialect from the extension of a file name
+    ///
+    /// If the extension ends with:
+    ///     .csv:      comma separated value format
+    ///     .tsv|.txt: tab separated value format
+    ///
+    /// A compression extension such as .gz after it is ignored
+    pub fn from_extension(filename: &str) -> Option<Self> {
+        let filename = strip_compression_extension(filename);
+
+        if filename.ends_with("csv") {
+            Some(Dialect::csv())
+        } else if filename.ends_with("tsv") || filename.ends_with("txt") {
+            Some(Dialect::tsv())
+        } else {
+            None
+        }
+    }
+
+    /// Test whether lines are skipped at the start of the input
+    pub fn has_preamble(&self) -> bool {
+        self.skip_lines > 0 || self.skip_until.is_some()
+    }
+
+    /// Check that the quote and escape characters differ from the delimiter and the
+    /// characters that end a record
+    ///
+    /// Fails with InvalidInput otherwise as the fields could not be told apart
+    pub fn validate(&self) -> io::Result<()> {
+        for (name, c) in [ ("quote", Some(self.quote)), ("escape", self.escape) ].iter() {
+            match c {
+                Some(c) if *c == self.delimiter =>
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                              format!("the {} character is the delimiter", name))),
+                Some(c) if self.is_terminator(*c) =>
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                              format!("the {} character ends a record", name))),
+                _ => ()
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Test whether a character ends a record
+    pub fn is_terminator(&self, c: char) -> bool {
+        match self.terminator {
+            Terminator::Default => c == '\n' || c == '\r',
+            Terminator::Char(t) => c == t,
+        }
+    }
+
+    /// The character used to terminate the last record at the end of the input
+    pub fn terminator_char(&self) -> char {
+        match self.terminator {
+            Terminator::Default => '\n',
+            Terminator::Char(t) => t,
+        }
+    }
+
+    /// Test whether a field in the header or data should be trimmed
+    pub fn trims(&self, header: bool) -> bool {
+        match self.trim {
+            Trim::None => false,
+            Trim::Headers => header,
+            Trim::Fields => !header,
+            Trim::All => true,
+        }
+    }
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect::csv()
+    }
+}
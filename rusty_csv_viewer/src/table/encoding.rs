@@ -0,0 +1,433 @@
+use std::char;
+use std::fmt::{Display, Formatter};
+use std::fmt::Result as FmtResult;
+use std::io::{self, Read};
+
+/// Number of bytes sampled from the start of the input to detect the encoding
+pub const DETECT_SAMPLE_SIZE: usize = 16 * 1024;
+/// Number of bytes decoded at a time
+const DECODE_CHUNK_SIZE: usize = 64 * 1024;
+
+const BOM_UTF8: &[u8] = &[0xEF, 0xBB, 0xBF];
+const BOM_UTF16LE: &[u8] = &[0xFF, 0xFE];
+const BOM_UTF16BE: &[u8] = &[0xFE, 0xFF];
+
+/// Characters for bytes 0x80 to 0x9F in Windows-1252. The rest of the range matches Latin-1
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{81}',   '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{8D}',   '\u{017D}', '\u{8F}',
+    '\u{90}',   '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{9D}',   '\u{017E}', '\u{0178}',
+];
+
+/// Character encodings that can be decoded
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+    Latin1,
+}
+
+impl Encoding {
+    /// The byte order mark written at the start of a file in this encoding
+    pub fn bom(&self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => BOM_UTF8,
+            Encoding::Utf16Le => BOM_UTF16LE,
+            Encoding::Utf16Be => BOM_UTF16BE,
+            Encoding::Windows1252 | Encoding::Latin1 => &[],
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Encoding::Utf8 => write!(f, "UTF-8"),
+            Encoding::Utf16Le => write!(f, "UTF-16LE"),
+            Encoding::Utf16Be => write!(f, "UTF-16BE"),
+            Encoding::Windows1252 => write!(f, "Windows-1252"),
+            Encoding::Latin1 => write!(f, "ISO-8859-1"),
+        }
+    }
+}
+
+/// The encoding of an input and whether it started with a byte order mark
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct DetectedEncoding {
+    pub encoding: Encoding,
+    pub bom: bool,
+}
+
+impl Display for DetectedEncoding {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if self.bom {
+            write!(f, "{} BOM", self.encoding)
+        } else {
+            write!(f, "{}", self.encoding)
+        }
+    }
+}
+
+/// Detect the encoding of a sample from the start of the input
+///
+/// A byte order mark always wins. Otherwise UTF-16 is recognised by the share of zero bytes
+/// in odd or even positions, valid UTF-8 is taken as UTF-8 and anything else falls back to a
+/// single-byte encoding. Bytes 0x80 to 0x9F are only printable in Windows-1252 so their
+/// presence selects it over Latin-1.
+pub fn detect_encoding(sample: &[u8]) -> DetectedEncoding {
+    for encoding in [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be].iter() {
+        if sample.starts_with(encoding.bom()) {
+            return DetectedEncoding { encoding: *encoding, bom: true };
+        }
+    }
+
+    let encoding = if let Some(utf16) = detect_utf16(sample) {
+        utf16
+    } else if is_utf8(sample) {
+        Encoding::Utf8
+    } else if sample.iter().any(|b| (0x80..=0x9F).contains(b)) {
+        Encoding::Windows1252
+    } else {
+        Encoding::Latin1
+    };
+
+    DetectedEncoding { encoding, bom: false }
+}
+
+/// Guess UTF-16 without a byte order mark from the position of zero bytes
+fn detect_utf16(sample: &[u8]) -> Option<Encoding> {
+    let pairs = sample.len() / 2;
+
+    if pairs < 2 {
+        return None;
+    }
+
+    let even_zeros = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_zeros = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+
+    // mostly ascii text has a zero in every other byte
+    if odd_zeros * 10 >= pairs * 4 && even_zeros * 10 < pairs {
+        Some(Encoding::Utf16Le)
+    } else if even_zeros * 10 >= pairs * 4 && odd_zeros * 10 < pairs {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Test for valid UTF-8 allowing a sequence to be cut off at the end of a full sample
+fn is_utf8(sample: &[u8]) -> bool {
+    match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && sample.len() >= DETECT_SAMPLE_SIZE
+    }
+}
+
+/// Reader that decodes its input into UTF-8
+///
+/// The encoding is detected from the start of the input unless it is given explicitly.
+/// A byte order mark is removed so it does not end up in the first field.
+/// Input detected as UTF-8 without a byte order mark is read as Windows-1252 from the
+/// first invalid sequence on, as the sample it was detected from can be all ASCII, so
+/// encoding() should be asked again once the input has been read.
+/// Any other invalid sequences are replaced with U+FFFD.
+pub struct DecodeReader<R> {
+    reader: R,
+    detected: DetectedEncoding,
+    /// The input was detected as UTF-8 without a byte order mark and can still fall back
+    fallback: bool,
+    /// Raw bytes that have not been decoded yet
+    raw: Vec<u8>,
+    /// Decoded bytes that have not been returned yet
+    decoded: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> DecodeReader<R> {
+    pub fn new(mut reader: R, encoding: Option<Encoding>) -> io::Result<Self> {
+        let mut raw = Vec::with_capacity(DETECT_SAMPLE_SIZE);
+        reader.by_ref().take(DETECT_SAMPLE_SIZE as u64).read_to_end(&mut raw)?;
+
+        let mut detected = detect_encoding(&raw);
+
+        // an explicit encoding only keeps the byte order mark if it matches
+        if let Some(encoding) = encoding {
+            detected = DetectedEncoding {
+                encoding,
+                bom: detected.bom && detected.encoding == encoding
+            };
+        }
+
+        if detected.bom {
+            raw.drain(..detected.encoding.bom().len());
+        }
+
+        let fallback = encoding.is_none() && detected == DetectedEncoding { encoding: Encoding::Utf8, bom: false };
+
+        Ok(DecodeReader {
+            reader,
+            detected,
+            fallback,
+            raw,
+            decoded: Vec::new(),
+            pos: 0,
+            eof: false,
+        })
+    }
+
+    /// The encoding used to decode the input
+    pub fn encoding(&self) -> DetectedEncoding {
+        self.detected
+    }
+
+    /// Read more raw bytes and decode as many of them as possible
+    fn fill_decoded(&mut self) -> io::Result<()> {
+        self.decoded.clear();
+        self.pos = 0;
+
+        while self.decoded.is_empty() && !(self.eof && self.raw.is_empty()) {
+            if !self.eof {
+                let n = self.reader.by_ref().take(DECODE_CHUNK_SIZE as u64).read_to_end(&mut self.raw)?;
+                self.eof = n == 0;
+            }
+
+            let used = match self.detected.encoding {
+                Encoding::Utf8 if self.fallback => match std::str::from_utf8(&self.raw) {
+                    Err(e) if e.error_len().is_some() || self.eof => {
+                        self.decoded.extend_from_slice(&self.raw[..e.valid_up_to()]);
+                        self.detected.encoding = Encoding::Windows1252;
+                        self.fallback = false;
+                        e.valid_up_to()
+                    },
+                    _ => decode_utf8(&self.raw, self.eof, &mut self.decoded),
+                },
+                Encoding::Utf8 => decode_utf8(&self.raw, self.eof, &mut self.decoded),
+                Encoding::Utf16Le => decode_utf16(&self.raw, self.eof, u16::from_le_bytes, &mut self.decoded),
+                Encoding::Utf16Be => decode_utf16(&self.raw, self.eof, u16::from_be_bytes, &mut self.decoded),
+                Encoding::Windows1252 => decode_single_byte(&self.raw, true, &mut self.decoded),
+                Encoding::Latin1 => decode_single_byte(&self.raw, false, &mut self.decoded),
+            };
+
+            self.raw.drain(..used);
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.decoded.len() {
+            self.fill_decoded()?;
+        }
+
+        let n = std::cmp::min(buf.len(), self.decoded.len() - self.pos);
+        buf[..n].copy_from_slice(&self.decoded[self.pos..self.pos + n]);
+        self.pos += n;
+
+        Ok(n)
+    }
+}
+
+/// Copy valid UTF-8 and replace invalid sequences
+///
+/// Returns the number of bytes used. An incomplete sequence at the end is kept for the next
+/// call unless the input has ended.
+fn decode_utf8(raw: &[u8], eof: bool, out: &mut Vec<u8>) -> usize {
+    let mut used = 0;
+
+    while used < raw.len() {
+        match std::str::from_utf8(&raw[used..]) {
+            Ok(s) => {
+                out.extend_from_slice(s.as_bytes());
+                used = raw.len();
+            },
+            Err(e) => {
+                out.extend_from_slice(&raw[used..used + e.valid_up_to()]);
+                used += e.valid_up_to();
+
+                match e.error_len() {
+                    Some(n) => {
+                        out.extend_from_slice("\u{FFFD}".as_bytes());
+                        used += n;
+                    },
+                    None if eof => {
+                        out.extend_from_slice("\u{FFFD}".as_bytes());
+                        used = raw.len();
+                    },
+                    None => break
+                }
+            }
+        }
+    }
+
+    used
+}
+
+/// Decode UTF-16 code units with the given byte order
+///
+/// Returns the number of bytes used. An odd byte or a lone high surrogate at the end is kept
+/// for the next call unless the input has ended.
+fn decode_utf16(raw: &[u8], eof: bool, unit: fn([u8; 2]) -> u16, out: &mut Vec<u8>) -> usize {
+    let mut units = raw.chunks_exact(2).map(|c| unit([c[0], c[1]])).collect::<Vec<_>>();
+    let mut used = units.len() * 2;
+
+    // keep a high surrogate that is waiting for its pair
+    if !eof && units.last().map_or(false, |u| (0xD800..0xDC00).contains(u)) {
+        units.pop();
+        used -= 2;
+    }
+
+    let mut buf = [0u8; 4];
+
+    for c in char::decode_utf16(units.into_iter()) {
+        let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+        out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+    }
+
+    if eof && used < raw.len() {
+        out.extend_from_slice("\u{FFFD}".as_bytes());
+        used = raw.len();
+    }
+
+    used
+}
+
+/// Decode Windows-1252 or Latin-1 where every byte is one character
+fn decode_single_byte(raw: &[u8], windows_1252: bool, out: &mut Vec<u8>) -> usize {
+    let mut buf = [0u8; 4];
+
+    for b in raw.iter() {
+        let c = match *b {
+            0x80..=0x9F if windows_1252 => WINDOWS_1252_HIGH[(*b - 0x80) as usize],
+            _ => *b as char
+        };
+
+        out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+    }
+
+    raw.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::*;
+
+    fn decode_all(input: &[u8], encoding: Option<Encoding>) -> (DetectedEncoding, String) {
+        let mut r = DecodeReader::new(input, encoding).expect("decoder error");
+        let mut s = String::new();
+        r.read_to_string(&mut s).expect("read error");
+        (r.encoding(), s)
+    }
+
+    fn utf16(s: &str, le: bool) -> Vec<u8> {
+        s.encode_utf16()
+            .flat_map(|u| if le { u.to_le_bytes().to_vec() } else { u.to_be_bytes().to_vec() })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_encoding_bom() {
+        let d = detect_encoding(b"\xEF\xBB\xBFName");
+        assert_eq!(d, DetectedEncoding { encoding: Encoding::Utf8, bom: true });
+
+        let d = detect_encoding(b"\xFF\xFEN\x00");
+        assert_eq!(d, DetectedEncoding { encoding: Encoding::Utf16Le, bom: true });
+
+        let d = detect_encoding(b"\xFE\xFF\x00N");
+        assert_eq!(d, DetectedEncoding { encoding: Encoding::Utf16Be, bom: true });
+    }
+
+    #[test]
+    fn test_detect_encoding_no_bom() {
+        assert_eq!(detect_encoding("Name,Größe".as_bytes()).encoding, Encoding::Utf8);
+        assert_eq!(detect_encoding(&utf16("Name,Value", true)).encoding, Encoding::Utf16Le);
+        assert_eq!(detect_encoding(&utf16("Name,Value", false)).encoding, Encoding::Utf16Be);
+        assert_eq!(detect_encoding(b"caf\xE9 \x80 5").encoding, Encoding::Windows1252);
+        assert_eq!(detect_encoding(b"caf\xE9").encoding, Encoding::Latin1);
+    }
+
+    #[test]
+    fn test_decode_strips_utf8_bom() {
+        let (d, s) = decode_all(b"\xEF\xBB\xBFName,Value\n", None);
+
+        assert_eq!(d.bom, true);
+        assert_eq!(s, "Name,Value\n");
+    }
+
+    #[test]
+    fn test_decode_utf16() {
+        let mut input = BOM_UTF16LE.to_vec();
+        input.extend(utf16("Näme,😀\n", true));
+        let (d, s) = decode_all(&input, None);
+
+        assert_eq!(d, DetectedEncoding { encoding: Encoding::Utf16Le, bom: true });
+        assert_eq!(s, "Näme,😀\n");
+
+        let (_, s) = decode_all(&utf16("a,b\nc,d\n", false), None);
+        assert_eq!(s, "a,b\nc,d\n");
+    }
+
+    #[test]
+    fn test_decode_single_byte() {
+        let (d, s) = decode_all(b"\x93caf\xE9\x94", None);
+
+        assert_eq!(d.encoding, Encoding::Windows1252);
+        assert_eq!(s, "\u{201C}café\u{201D}");
+
+        let (d, s) = decode_all(b"caf\xE9", Some(Encoding::Latin1));
+
+        assert_eq!(d.encoding, Encoding::Latin1);
+        assert_eq!(s, "café");
+    }
+
+    #[test]
+    fn test_decode_invalid_utf8_is_replaced() {
+        let (_, s) = decode_all(b"abc\xFFdef\xE2\x82", Some(Encoding::Utf8));
+        assert_eq!(s, "abc\u{FFFD}def\u{FFFD}");
+    }
+
+    #[test]
+    fn test_decode_falls_back_to_windows_1252() {
+        // the invalid sequence is well past the sample the encoding was detected from
+        let mut input = "Name,Value\n".repeat(DETECT_SAMPLE_SIZE / 8).into_bytes();
+        input.extend_from_slice("Ä,1\n".as_bytes());
+        input.extend_from_slice(b"Andr\xE9,\x80 2\n");
+
+        let mut r = DecodeReader::new(&input[..], None).expect("decoder error");
+        assert_eq!(r.encoding().encoding, Encoding::Utf8);
+
+        let mut s = String::new();
+        r.read_to_string(&mut s).expect("read error");
+
+        assert_eq!(r.encoding(), DetectedEncoding { encoding: Encoding::Windows1252, bom: false });
+        assert!(s.ends_with("Ä,1\nAndré,\u{20AC} 2\n"));
+
+        // a byte order mark or an explicit encoding is kept
+        let mut bom = BOM_UTF8.to_vec();
+        bom.extend_from_slice(b"caf\xE9");
+        assert_eq!(decode_all(&bom, None), (DetectedEncoding { encoding: Encoding::Utf8, bom: true }, "caf\u{FFFD}".to_string()));
+    }
+
+    #[test]
+    fn test_decode_sequences_across_reads() {
+        // a tiny buffer splits multi-byte sequences between reads of the decoder
+        let input = "ä€😀".repeat(DECODE_CHUNK_SIZE / 3);
+        let mut r = BufReader::with_capacity(5, DecodeReader::new(input.as_bytes(), None).unwrap());
+        let mut s = String::new();
+        r.read_to_string(&mut s).expect("read error");
+
+        assert_eq!(s, input);
+
+        let le = utf16(&input, true);
+        let (_, s) = decode_all(&le, Some(Encoding::Utf16Le));
+
+        assert_eq!(s, input);
+    }
+}
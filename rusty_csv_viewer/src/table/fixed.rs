@@ -0,0 +1,285 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::table::data::{RecordSource, TableData};
+use crate::table::encoding::{DecodeReader, DetectedEncoding, Encoding};
+use crate::table::reader::open_file;
+
+/// Number of lines sampled to infer the columns of a fixed-width file
+pub const FIXED_SAMPLE_LINES: usize = 200;
+
+/// A column of a fixed-width file
+#[derive(Debug,Clone,PartialEq,Deserialize,Serialize)]
+pub struct FixedColumn {
+    /// Header of the column. Left empty when the names come from the first line
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// 0-based character position where the column starts in a line
+    pub start: usize,
+    /// Number of characters in the column
+    pub width: usize,
+}
+
+impl FixedColumn {
+    pub fn new(start: usize, width: usize) -> Self {
+        FixedColumn { name: String::new(), start, width }
+    }
+
+    pub fn named(name: &str, start: usize, width: usize) -> Self {
+        FixedColumn { name: name.to_string(), start, width }
+    }
+}
+
+/// Where the columns of a fixed-width file are and where their names come from
+///
+/// Layouts are saved as JSON so a layout worked out once can be reused for every
+/// extract in the same format
+#[derive(Debug,Clone,PartialEq,Default,Deserialize,Serialize)]
+pub struct FixedLayout {
+    pub columns: Vec<FixedColumn>,
+    /// The first line holds the column names. Otherwise the names of the columns are
+    /// the header, if any of them has one
+    #[serde(default)]
+    pub header: bool,
+}
+
+impl FixedLayout {
+    pub fn new(columns: Vec<FixedColumn>, header: bool) -> Self {
+        FixedLayout { columns, header }
+    }
+
+    /// Read a layout saved with save
+    pub fn load(filename: &str) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(filename)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Write the layout to a file as JSON
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(filename)?);
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+
+    /// Infer the columns from runs of whitespace that line up across every sampled line
+    ///
+    /// A column spans the characters from where any line has text in it up to the next
+    /// position that is blank in every line. Blank lines are ignored
+    pub fn infer(lines: &[&str], header: bool) -> Self {
+        let mut used: Vec<bool> = Vec::new();
+
+        for line in lines {
+            for (i, c) in line.chars().enumerate() {
+                if i >= used.len() {
+                    used.resize(i + 1, false);
+                }
+
+                used[i] |= !c.is_whitespace();
+            }
+        }
+
+        let mut columns = Vec::new();
+        let mut start = None;
+
+        for (i, used) in used.iter().chain([false].iter()).enumerate() {
+            match (start, *used) {
+                (None, true) => start = Some(i),
+                (Some(s), false) => {
+                    columns.push(FixedColumn::new(s, i - s));
+                    start = None;
+                },
+                _ => {}
+            }
+        }
+
+        FixedLayout { columns, header }
+    }
+
+    /// Cut a line into the fields of each column with surrounding whitespace removed
+    ///
+    /// Columns past the end of a short line are empty
+    pub fn split(&self, line: &str) -> Vec<String> {
+        let bounds = line.char_indices()
+            .map(|(i, _)| i)
+            .chain([line.len()].iter().copied())
+            .collect::<Vec<_>>();
+        let chars = bounds.len() - 1;
+
+        self.columns.iter()
+            .map(|c| {
+                let start = bounds[c.start.min(chars)];
+                let end = bounds[(c.start + c.width).min(chars)];
+                line[start..end].trim().to_string()
+            })
+            .collect()
+    }
+
+    /// The names of the columns or None if none of them has one
+    fn names(&self) -> Option<Vec<String>> {
+        if self.columns.iter().any(|c| !c.name.is_empty()) {
+            Some(self.columns.iter().map(|c| c.name.clone()).collect())
+        } else {
+            None
+        }
+    }
+}
+
+/// Read a fixed-width file in the given encoding or a detected one if None
+///
+/// Returns the encoding the file was decoded with alongside the table
+pub fn from_fixed_file(filename: &str, layout: &FixedLayout, encoding: Option<Encoding>)
+    -> io::Result<(TableData, DetectedEncoding)>
+{
+    let mut decoder = DecodeReader::new(open_file(filename)?, encoding)?;
+    let table = from_fixed_reader(BufReader::new(&mut decoder), layout)?;
+
+    Ok((table, decoder.encoding()))
+}
+
+/// Infer the layout of a fixed-width file from its first FIXED_SAMPLE_LINES lines
+pub fn infer_fixed_file(filename: &str, header: bool) -> io::Result<FixedLayout> {
+    let decoder = DecodeReader::new(open_file(filename)?, None)?;
+    let lines = BufReader::new(decoder).lines()
+        .take(FIXED_SAMPLE_LINES)
+        .collect::<io::Result<Vec<_>>>()?;
+
+    Ok(FixedLayout::infer(&lines.iter().map(|l| l.as_str()).collect::<Vec<_>>(), header))
+}
+
+/// Read fixed-width data from any buffered reader
+///
+/// Every non-blank line is a row. Lines end with '\n' or '\r\n'
+pub fn from_fixed_reader<R: BufRead>(mut reader: R, layout: &FixedLayout) -> io::Result<TableData> {
+    let mut table = TableData::new();
+    let cols = layout.columns.len();
+
+    if let Some(mut names) = layout.names().filter(|_| !layout.header) {
+        table.set_header(&mut names);
+    }
+
+    let mut line = String::new();
+    let mut source = RecordSource { line: 1, offset: 0 };
+
+    loop {
+        line.clear();
+        let n = reader.read_line(&mut line)?;
+
+        if n == 0 {
+            break;
+        }
+
+        let text = if line.ends_with("\r\n") {
+            table.line_endings.crlf += 1;
+            &line[..line.len() - 2]
+        } else if line.ends_with('\n') {
+            table.line_endings.lf += 1;
+            &line[..line.len() - 1]
+        } else {
+            &line[..]
+        };
+
+        if !text.trim().is_empty() {
+            let mut fields = layout.split(text);
+
+            if layout.header && !table.has_headers() {
+                table.set_header(&mut fields);
+            } else if cols > 0 {
+                table.set_data(&mut fields, cols);
+                table.sources.push(source);
+            }
+        }
+
+        source.line += 1;
+        source.offset += n;
+    }
+
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    macro_rules! make_strvec {
+        [ $($a:expr),+ ] => {
+            vec![ $($a.to_owned()),+ ]
+        }
+    }
+
+    const EXTRACT: &str =
+        "ID   NAME        AMOUNT\r\n\
+         00001SMITH J       12.50\r\n\
+         \r\n\
+         00002NGUYEN THI   300.00\r\n\
+         00003BROWN";
+
+    #[test]
+    fn test_fixed_reader_with_header_line() {
+        let layout = FixedLayout::new(vec![ FixedColumn::new(0, 5), FixedColumn::new(5, 12),
+                                            FixedColumn::new(17, 7) ], true);
+        let r = from_fixed_reader(EXTRACT.as_bytes(), &layout).expect("read error");
+
+        assert_eq!(r.header(), &make_strvec![ "ID", "NAME", "AMOUNT" ]);
+        assert_eq!(r.data(), &make_strvec![ "00001", "SMITH J", "12.50",
+                                            "00002", "NGUYEN THI", "300.00",
+                                            "00003", "BROWN", "" ]);
+        assert_eq!(r.source(1), Some(RecordSource { line: 4, offset: 53 }));
+        assert_eq!(r.line_endings.crlf, 4);
+    }
+
+    #[test]
+    fn test_fixed_reader_named_columns() {
+        let layout = FixedLayout::new(vec![ FixedColumn::named("Code", 0, 2), FixedColumn::named("Größe", 2, 3) ], false);
+        let r = from_fixed_reader("aäöüb\nc".as_bytes(), &layout).expect("read error");
+
+        assert_eq!(r.header(), &make_strvec![ "Code", "Größe" ]);
+        assert_eq!(r.data(), &make_strvec![ "aä", "öüb", "c", "" ]);
+    }
+
+    #[test]
+    fn test_infer_layout() {
+        let lines = EXTRACT.lines().skip(1).collect::<Vec<_>>();
+        let layout = FixedLayout::infer(&lines, false);
+
+        // the id and name touch so they can not be told apart
+        assert_eq!(layout.columns, vec![ FixedColumn::new(0, 15), FixedColumn::new(18, 6) ]);
+
+        let lines = [ "ID  NAME   AMOUNT", "1   SMITH   12.50", "22  BROWN  300.00" ];
+        let layout = FixedLayout::infer(&lines, true);
+
+        assert_eq!(layout.columns, vec![ FixedColumn::new(0, 2), FixedColumn::new(4, 5),
+                                         FixedColumn::new(11, 6) ]);
+        assert_eq!(layout.split(lines[1]), make_strvec![ "1", "SMITH", "12.50" ]);
+    }
+
+    #[test]
+    fn test_layout_save_and_load() {
+        let f = "fixed_layout.json";
+        let layout = FixedLayout::new(vec![ FixedColumn::named("ID", 0, 5), FixedColumn::new(5, 12) ], false);
+
+        layout.save(f).expect("save failed");
+        let saved = fs::read_to_string(f).expect("read failed");
+        let r = FixedLayout::load(f).expect("load failed");
+        fs::remove_file(f).expect("remove failed");
+
+        assert_eq!(r, layout);
+        assert!(!saved.contains(r#""name": """#));
+    }
+
+    #[test]
+    fn test_fixed_file_inferred() {
+        let f = "fixed_data.txt";
+        fs::write(f, "ID  NAME   AMOUNT\n1   SMITH   12.50\n22  BROWN  300.00\n").expect("write failed");
+
+        let layout = infer_fixed_file(f, true).expect("infer failed");
+        let (r, _) = from_fixed_file(f, &layout, None).expect("read error");
+        fs::remove_file(f).expect("remove failed");
+
+        assert_eq!(r.header(), &make_strvec![ "ID", "NAME", "AMOUNT" ]);
+        assert_eq!(r.rows(), 2);
+        assert_eq!(r.data()[3..], make_strvec![ "22", "BROWN", "300.00" ][..]);
+    }
+}
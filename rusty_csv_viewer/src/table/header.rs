@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+
+/// Type of a single field value used to profile a column
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum FieldType {
+    Empty,
+    Number,
+    Date,
+    Boolean,
+    Text,
+}
+
+impl FieldType {
+    /// Infer the type of a field from its text
+    pub fn of(value: &str) -> Self {
+        let value = value.trim();
+
+        if value.is_empty() {
+            FieldType::Empty
+        } else if value.parse::<f64>().is_ok() && value.chars().any(|c| c.is_ascii_digit()) {
+            FieldType::Number
+        } else if is_date(value) {
+            FieldType::Date
+        } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+            FieldType::Boolean
+        } else {
+            FieldType::Text
+        }
+    }
+}
+
+/// Decide whether the first record is a header by comparing its type profile to the
+/// records that follow it
+///
+/// The first record must be made of unique, non-empty text fields. Each column then votes:
+///     numeric, date or boolean data under a text name:  header
+///     text data of a fixed length under a name of another length: header
+///     text data of a fixed length under a name of the same length: not a header
+///
+/// Returns true when the votes for a header outweigh the votes against it
+pub fn detect_header(records: &[Vec<String>]) -> bool {
+    if records.len() < 2 {
+        return false;
+    }
+
+    let first = &records[0];
+    let mut names = HashSet::new();
+
+    for name in first.iter() {
+        if FieldType::of(name) != FieldType::Text || !names.insert(name.trim()) {
+            return false;
+        }
+    }
+
+    let mut votes = 0;
+
+    for (col, name) in first.iter().enumerate() {
+        let values = records[1..].iter()
+            .filter_map(|r| r.get(col))
+            .filter(|v| FieldType::of(v) != FieldType::Empty)
+            .collect::<Vec<_>>();
+
+        if values.is_empty() {
+            continue;
+        }
+
+        match column_type(&values) {
+            Some(FieldType::Number) | Some(FieldType::Date) | Some(FieldType::Boolean) => votes += 1,
+            Some(FieldType::Text) => {
+                let len = values[0].chars().count();
+
+                if values.iter().all(|v| v.chars().count() == len) {
+                    votes += if name.chars().count() != len { 1 } else { -1 };
+                }
+            },
+            _ => {}
+        }
+    }
+
+    votes > 0
+}
+
+/// Find the type shared by at least 80% of the non-empty values of a column
+fn column_type(values: &[&String]) -> Option<FieldType> {
+    let types = [FieldType::Number, FieldType::Date, FieldType::Boolean, FieldType::Text];
+
+    types.iter()
+        .map(|t| (*t, values.iter().filter(|v| FieldType::of(v) == *t).count()))
+        .find(|(_, n)| *n * 5 >= values.len() * 4)
+        .map(|(t, _)| t)
+}
+
+/// Test whether a value looks like a date with an optional time
+///
+/// Accepts year first (2021-03-04, 2021/03/04) and year last (04/03/2021, 04.03.2021) forms
+fn is_date(value: &str) -> bool {
+    let date = value.splitn(2, |c| c == 'T' || c == ' ').next().unwrap_or("");
+    let parts = date.split(|c| c == '-' || c == '/' || c == '.').collect::<Vec<_>>();
+
+    if parts.len() != 3 || parts.iter().any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_digit())) {
+        return false;
+    }
+
+    let nums = parts.iter().map(|p| p.parse::<u32>().unwrap_or(0)).collect::<Vec<_>>();
+    let valid = |month: u32, day: u32| (1..=12).contains(&month) && (1..=31).contains(&day);
+
+    match (parts[0].len(), parts[1].len(), parts[2].len()) {
+        (4, 1..=2, 1..=2) => valid(nums[1], nums[2]),
+        // day and month order is ambiguous so accept either
+        (1..=2, 1..=2, 4) => valid(nums[0], nums[1]) || valid(nums[1], nums[0]),
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! make_records {
+        [ $([ $($a:expr),+ ]),+ ] => {
+            vec![ $(vec![ $($a.to_owned()),+ ]),+ ]
+        }
+    }
+
+    #[test]
+    fn test_field_type() {
+        assert_eq!(FieldType::of(""), FieldType::Empty);
+        assert_eq!(FieldType::of(" 10.5 "), FieldType::Number);
+        assert_eq!(FieldType::of("-3"), FieldType::Number);
+        assert_eq!(FieldType::of("2021-03-04"), FieldType::Date);
+        assert_eq!(FieldType::of("2021-03-04T10:20:00"), FieldType::Date);
+        assert_eq!(FieldType::of("31/12/2020"), FieldType::Date);
+        assert_eq!(FieldType::of("12.31.2020"), FieldType::Date);
+        assert_eq!(FieldType::of("13/13/2020"), FieldType::Text);
+        assert_eq!(FieldType::of("TRUE"), FieldType::Boolean);
+        assert_eq!(FieldType::of("Name"), FieldType::Text);
+        assert_eq!(FieldType::of("inf"), FieldType::Text);
+    }
+
+    #[test]
+    fn test_detect_header_numeric_and_date_columns() {
+        let r = make_records![ ["Name", "Amount", "Date"],
+                               ["apple", "10", "2021-01-01"],
+                               ["pear", "", "2021-01-02"] ];
+        assert_eq!(detect_header(&r), true);
+    }
+
+    #[test]
+    fn test_detect_header_first_row_is_data() {
+        let r = make_records![ ["apple", "10"], ["pear", "20"] ];
+        assert_eq!(detect_header(&r), false);
+    }
+
+    #[test]
+    fn test_detect_header_duplicate_or_empty_names() {
+        let r = make_records![ ["Name", "Name"], ["a", "1"] ];
+        assert_eq!(detect_header(&r), false);
+
+        let r = make_records![ ["Name", ""], ["a", "1"] ];
+        assert_eq!(detect_header(&r), false);
+    }
+
+    #[test]
+    fn test_detect_header_text_columns_by_length() {
+        let r = make_records![ ["Code", "Country"], ["AB1", "AU"], ["CD2", "NZ"] ];
+        assert_eq!(detect_header(&r), true);
+
+        let r = make_records![ ["abc", "xy"], ["def", "zw"], ["ghi", "uv"] ];
+        assert_eq!(detect_header(&r), false);
+    }
+
+    #[test]
+    fn test_detect_header_single_record() {
+        let r = make_records![ ["Name", "Value"] ];
+        assert_eq!(detect_header(&r), false);
+    }
+}
@@ -0,0 +1,619 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+use crate::table::compression::strip_compression_extension;
+use crate::table::data::{Location, RecordSource, TableData, TableDataValidationError};
+use crate::table::encoding::{DecodeReader, DetectedEncoding, Encoding};
+use crate::table::reader::{open_file, snippet};
+
+type TableResult<T> = Result<T, TableDataValidationError>;
+
+/// Separates the keys of nested objects in a flattened column name e.g. "user.address.city"
+pub const KEY_SEPARATOR: &str = ".";
+
+/// Extensions of files that hold JSON tables
+pub const JSON_EXTENSIONS: [&str; 3] = [".json", ".jsonl", ".ndjson"];
+
+/// Layouts of JSON input that hold a table
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum JsonLayout {
+    /// JSON Lines (NDJSON), one object per line
+    Lines,
+    /// An array of objects, one per row e.g. `[{"a":1},{"a":2}]` as written by pandas with
+    /// orient="records". A single object is read as one row
+    Records,
+    /// An object with an array of column names and an array of rows e.g.
+    /// `{"columns":["a"],"index":[0],"data":[[1]]}` as written by pandas with orient="split".
+    /// The index is ignored
+    Split,
+}
+
+/// How nested values are turned into columns
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Flatten {
+    /// Nested objects and arrays are kept as JSON text
+    None,
+    /// Nested objects become columns named by their dotted key paths and arrays are kept as JSON text
+    Objects,
+    /// Nested objects and arrays become columns. Array elements are keyed by their index e.g. "tags.0"
+    All,
+}
+
+impl Default for Flatten {
+    fn default() -> Self {
+        Flatten::Objects
+    }
+}
+
+/// How JSON input is read
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct JsonOptions {
+    /// Skip records that are not JSON objects and keep the errors with the table instead
+    /// of stopping at the first one
+    pub lenient: bool,
+    /// Layout of the input or None to detect it, see from_json_reader
+    pub layout: Option<JsonLayout>,
+    pub flatten: Flatten,
+}
+
+/// Test whether a file holds JSON from its extension, ignoring a compression extension
+pub fn is_json_file(filename: &str) -> bool {
+    let filename = strip_compression_extension(filename).to_lowercase();
+    JSON_EXTENSIONS.iter().any(|e| filename.ends_with(e))
+}
+
+/// Read a JSON file in any layout in the given encoding or a detected one if None
+///
+/// Returns the encoding the file was decoded with alongside the result
+pub fn from_json_file(filename: &str, options: &JsonOptions, encoding: Option<Encoding>)
+    -> io::Result<(TableResult<TableData>, DetectedEncoding)>
+{
+    let mut decoder = DecodeReader::new(open_file(filename)?, encoding)?;
+    let table = from_json_reader(BufReader::new(&mut decoder), options)?;
+
+    Ok((table, decoder.encoding()))
+}
+
+/// Read a JSON table in any layout from any buffered reader
+///
+/// Unless the options give the layout it is detected from the first non-blank line. An
+/// array holds records, an object with "columns" and "data" arrays is split and any other
+/// object that fits on the line starts JSON Lines. An object spread over several lines is
+/// split if it can be read as such or else a single record. JSON Lines is read a line at a
+/// time while the other layouts are read into memory in full
+pub fn from_json_reader<R: BufRead>(mut reader: R, options: &JsonOptions) -> io::Result<TableResult<TableData>> {
+    // blank lines before the first value are kept for the positions of the records
+    let mut head = String::new();
+
+    while head.trim().is_empty() {
+        if reader.read_line(&mut head)? == 0 {
+            break;
+        }
+    }
+
+    let layout = options.layout.or_else(|| detect_layout(&head));
+
+    if layout == Some(JsonLayout::Lines) {
+        return from_ndjson_reader(Cursor::new(head).chain(reader), options);
+    }
+
+    let mut text = head;
+    reader.read_to_string(&mut text)?;
+
+    let table = match layout {
+        Some(JsonLayout::Split) => read_split(&text, options),
+        Some(_) => read_records(&text, options),
+        None => match serde_json::from_str::<SplitDocument>(&text) {
+            Ok(document) => split_table(&text, document, options),
+            Err(_) => read_records(&text, options),
+        }
+    };
+
+    Ok(table)
+}
+
+/// Detect the layout from the first non-blank line. None for an object that does not fit on it
+fn detect_layout(head: &str) -> Option<JsonLayout> {
+    let line = head.trim();
+
+    if line.starts_with('[') {
+        return Some(JsonLayout::Records);
+    }
+
+    match serde_json::from_str::<Value>(line) {
+        Ok(Value::Object(object)) if is_split(&object) => Some(JsonLayout::Split),
+        Err(_) if line.starts_with('{') => None,
+        // anything else is left to the JSON Lines reader to report
+        _ => Some(JsonLayout::Lines),
+    }
+}
+
+fn is_split(object: &serde_json::Map<String, Value>) -> bool {
+    object.get("columns").map_or(false, Value::is_array) && object.get("data").map_or(false, Value::is_array)
+}
+
+/// Read a JSON Lines (NDJSON) file in the given encoding or a detected one if None
+///
+/// Returns the encoding the file was decoded with alongside the result
+pub fn from_ndjson_file(filename: &str, options: &JsonOptions, encoding: Option<Encoding>)
+    -> io::Result<(TableResult<TableData>, DetectedEncoding)>
+{
+    let mut decoder = DecodeReader::new(open_file(filename)?, encoding)?;
+    let table = from_ndjson_reader(BufReader::new(&mut decoder), options)?;
+
+    Ok((table, decoder.encoding()))
+}
+
+/// Read JSON Lines (NDJSON) from any buffered reader
+///
+/// Every non-blank line holds one object which becomes a row. Nested values are turned
+/// into columns as the options say. The header is the union of the keys of every object in
+/// the order they were first seen, and keys that are missing from an object are left
+/// empty. Only one line is held in memory at a time besides the table
+pub fn from_ndjson_reader<R: BufRead>(mut reader: R, options: &JsonOptions) -> io::Result<TableResult<TableData>> {
+    let mut table = TableData::new();
+    let mut columns = Columns::default();
+
+    let mut line = String::new();
+    let mut source = RecordSource { line: 1, offset: 0 };
+    // records read so far including those skipped in lenient mode
+    let mut records = 0;
+
+    loop {
+        line.clear();
+        let n = reader.read_line(&mut line)?;
+
+        if n == 0 {
+            break;
+        }
+
+        let text = if line.ends_with("\r\n") {
+            table.line_endings.crlf += 1;
+            &line[..line.len() - 2]
+        } else if line.ends_with('\n') {
+            table.line_endings.lf += 1;
+            &line[..line.len() - 1]
+        } else {
+            &line[..]
+        };
+
+        if !text.trim().is_empty() {
+            records += 1;
+
+            match parse_object(text, records, source) {
+                Ok(object) => columns.add_row(&mut table, flatten_record(&object, options.flatten), source),
+                Err(e) if options.lenient => table.errors.push(e),
+                Err(e) => return Ok(Err(e))
+            }
+        }
+
+        source.line += 1;
+        source.offset += n;
+    }
+
+    columns.set_header(&mut table);
+
+    Ok(Ok(table))
+}
+
+/// A document in the split layout. The rows are kept as text to locate errors in them
+#[derive(Deserialize)]
+struct SplitDocument<'a> {
+    columns: Vec<Value>,
+    #[serde(borrow)]
+    data: Vec<&'a RawValue>,
+}
+
+/// Read a document in the records layout
+fn read_records(text: &str, options: &JsonOptions) -> TableResult<TableData> {
+    let document: &RawValue = serde_json::from_str(text).map_err(|e| syntax_error(text, &e))?;
+
+    let records: Vec<&RawValue> = match document.get().as_bytes()[0] {
+        b'[' => serde_json::from_str(document.get()).map_err(|e| syntax_error(text, &e))?,
+        b'{' => vec![document],
+        _ => return Err(TableDataValidationError::JsonError {
+            row: 1,
+            message: "expected an array or an object".to_string(),
+            location: locate(text, START, index_of(text, document)),
+        })
+    };
+
+    let mut table = TableData::new();
+    let mut columns = Columns::default();
+
+    for (i, record) in records.into_iter().enumerate() {
+        let location = locate(text, START, index_of(text, record));
+        let source = RecordSource { line: location.line, offset: location.offset };
+
+        match serde_json::from_str(record.get()) {
+            Ok(Value::Object(object)) => columns.add_row(&mut table, flatten_record(&object, options.flatten), source),
+            _ => {
+                let e = TableDataValidationError::JsonError {
+                    row: i + 1,
+                    message: "expected an object".to_string(),
+                    location,
+                };
+
+                if !options.lenient {
+                    return Err(e);
+                }
+
+                table.errors.push(e);
+            }
+        }
+    }
+
+    columns.set_header(&mut table);
+
+    Ok(table)
+}
+
+/// Read a document in the split layout
+fn read_split(text: &str, options: &JsonOptions) -> TableResult<TableData> {
+    let document = serde_json::from_str(text).map_err(|e| syntax_error(text, &e))?;
+    split_table(text, document, options)
+}
+
+fn split_table(text: &str, document: SplitDocument, options: &JsonOptions) -> TableResult<TableData> {
+    let names = document.columns.iter().map(field_text).collect::<Vec<_>>();
+
+    let mut table = TableData::new();
+    let mut columns = Columns::default();
+
+    for (i, record) in document.data.into_iter().enumerate() {
+        let location = locate(text, START, index_of(text, record));
+        let source = RecordSource { line: location.line, offset: location.offset };
+        let row = i + 1;
+
+        let error = match serde_json::from_str(record.get()) {
+            Ok(Value::Array(values)) => {
+                let mut fields = Vec::new();
+
+                for (name, value) in names.iter().zip(values.iter()) {
+                    flatten_value(name.clone(), value, options.flatten, &mut fields);
+                }
+
+                // a lenient row that is too short is padded and one that is too long is cut
+                columns.add_row(&mut table, fields, source);
+
+                if values.len() == names.len() {
+                    continue;
+                }
+
+                TableDataValidationError::RowFieldCountMismatchError {
+                    row, expected: names.len(), found: values.len(), location
+                }
+            },
+            _ => TableDataValidationError::JsonError { row, message: "expected an array".to_string(), location }
+        };
+
+        if !options.lenient {
+            return Err(error);
+        }
+
+        table.errors.push(error);
+    }
+
+    columns.set_header(&mut table);
+
+    // the header of a document without rows comes straight from the column names
+    if !table.has_headers() && !names.is_empty() {
+        table.set_header(&mut names.clone());
+    }
+
+    Ok(table)
+}
+
+/// Position of the start of the input
+const START: RecordSource = RecordSource { line: 1, offset: 0 };
+
+/// Byte index of a value borrowed from the text
+fn index_of(text: &str, value: &RawValue) -> usize {
+    value.get().as_ptr() as usize - text.as_ptr() as usize
+}
+
+/// Turn a syntax error of a whole document into a validation error of the first row
+fn syntax_error(text: &str, e: &serde_json::Error) -> TableDataValidationError {
+    TableDataValidationError::JsonError {
+        row: 1,
+        message: e.to_string(),
+        location: locate(text, START, error_index(text, e)),
+    }
+}
+
+/// Byte index of the position of an error. serde_json counts lines and columns in bytes from 1
+fn error_index(text: &str, e: &serde_json::Error) -> usize {
+    let line_start = text.split_inclusive('\n')
+        .take(e.line().saturating_sub(1))
+        .map(|l| l.len())
+        .sum::<usize>();
+
+    line_start + e.column().saturating_sub(1)
+}
+
+/// Location of a byte index in text that starts at the given position in the input
+fn locate(text: &str, start: RecordSource, index: usize) -> Location {
+    let index = (0..=index.min(text.len())).rev().find(|i| text.is_char_boundary(*i)).unwrap_or(0);
+    let line_start = text[..index].rfind('\n').map_or(0, |i| i + 1);
+    let line = text[line_start..].split('\n').next().unwrap_or("");
+    let column = text[line_start..index].chars().count();
+
+    Location {
+        line: start.line + text[..line_start].matches('\n').count(),
+        offset: start.offset + index,
+        column: column + 1,
+        snippet: snippet(line, column),
+    }
+}
+
+/// Parse a line that should hold a JSON object
+fn parse_object(text: &str, row: usize, source: RecordSource) -> TableResult<serde_json::Map<String, Value>> {
+    let error = |message: String, index: usize| {
+        TableDataValidationError::JsonError { row, message, location: locate(text, source, index) }
+    };
+
+    match serde_json::from_str(text) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => {
+            let index = text.len() - text.trim_start().len();
+            Err(error("expected an object".to_string(), index))
+        },
+        Err(e) => Err(error(e.to_string(), error_index(text, &e)))
+    }
+}
+
+/// The columns found so far, by flattened key
+#[derive(Default)]
+struct Columns {
+    keys: Vec<String>,
+    index: HashMap<String, usize>,
+    /// Sources of the records without keys that came before the first key
+    keyless: Vec<RecordSource>,
+}
+
+impl Columns {
+    /// Add the fields of a record as a row, widening the table for keys that have not been seen before
+    fn add_row(&mut self, table: &mut TableData, fields: Vec<(String, String)>, source: RecordSource) {
+        let mut row = vec![String::new(); self.keys.len()];
+
+        for (key, value) in fields {
+            let col = match self.index.get(&key) {
+                Some(col) => *col,
+                None => {
+                    self.index.insert(key.clone(), self.keys.len());
+                    self.keys.push(key);
+                    row.push(String::new());
+                    self.keys.len() - 1
+                }
+            };
+
+            row[col] = value;
+        }
+
+        // nothing can be shown until a record has a key, and then records before it are empty rows
+        if self.keys.is_empty() {
+            self.keyless.push(source);
+            return;
+        }
+
+        if self.keys.len() > table.columns() {
+            table.widen(self.keys.len());
+        }
+
+        for keyless in self.keyless.drain(..) {
+            table.set_data(&mut vec![String::new(); self.keys.len()], self.keys.len());
+            table.sources.push(keyless);
+        }
+
+        table.set_data(&mut row, self.keys.len());
+        table.sources.push(source);
+    }
+
+    fn set_header(&mut self, table: &mut TableData) {
+        if !self.keys.is_empty() {
+            table.set_header(&mut self.keys.clone());
+        }
+    }
+}
+
+/// Flatten the members of an object into pairs of column names and field text
+fn flatten_record(object: &serde_json::Map<String, Value>, flatten: Flatten) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+
+    for (key, value) in object {
+        flatten_value(key.clone(), value, flatten, &mut fields);
+    }
+
+    fields
+}
+
+/// Flatten a value into pairs of dotted key paths and field text
+fn flatten_value(key: String, value: &Value, flatten: Flatten, out: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(nested) if flatten != Flatten::None => {
+            for (k, v) in nested {
+                flatten_value(format!("{}{}{}", key, KEY_SEPARATOR, k), v, flatten, out);
+            }
+        },
+        Value::Array(items) if flatten == Flatten::All => {
+            for (i, v) in items.iter().enumerate() {
+                flatten_value(format!("{}{}{}", key, KEY_SEPARATOR, i), v, flatten, out);
+            }
+        },
+        value => out.push((key, field_text(value))),
+    }
+}
+
+/// The text shown for a value. Null is empty and strings are shown without quotes
+fn field_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! make_strvec {
+        [ $($a:expr),+ ] => {
+            vec![ $($a.to_owned()),+ ]
+        }
+    }
+
+    fn parse(s: &str, lenient: bool) -> TableResult<TableData> {
+        from_ndjson_reader(s.as_bytes(), &JsonOptions { lenient, .. JsonOptions::default() }).expect("read error")
+    }
+
+    fn parse_json(s: &str, options: &JsonOptions) -> TableResult<TableData> {
+        from_json_reader(s.as_bytes(), options).expect("read error")
+    }
+
+    #[test]
+    fn test_ndjson_union_of_keys() {
+        let s = "{\"id\":1,\"level\":\"info\"}\n\
+                 \n\
+                 {\"id\":2,\"level\":\"warn\",\"user\":{\"name\":\"ann\",\"roles\":[\"a\",\"b\"]}}\r\n\
+                 {\"id\":3,\"level\":null,\"ok\":true}";
+        let r = parse(s, false).expect("parse error");
+
+        assert_eq!(r.header(), &make_strvec![ "id", "level", "user.name", "user.roles", "ok" ]);
+        assert_eq!(r.data(), &make_strvec![ "1", "info", "", "", "",
+                                            "2", "warn", "ann", "[\"a\",\"b\"]", "",
+                                            "3", "", "", "", "true" ]);
+        assert_eq!(r.source(2), Some(RecordSource { line: 4, offset: 90 }));
+        assert_eq!((r.line_endings.lf, r.line_endings.crlf), (2, 1));
+    }
+
+    #[test]
+    fn test_ndjson_invalid_line() {
+        let s = "{\"id\":1}\n{\"id\":\"é\" 2}\n[1,2]\n{\"id\":3}\n";
+        let e = parse(s, false).err().expect("no error");
+
+        assert_eq!(e.position(), (2, 1));
+        assert_eq!(e.location(), &Location { line: 2, offset: 20, column: 11,
+                                             snippet: "{\"id\":\"é\" 2}\n          ^".to_string() });
+
+        let r = parse(s, true).expect("parse error");
+
+        assert_eq!(r.data(), &make_strvec![ "1", "3" ]);
+        assert_eq!(r.errors().len(), 2);
+        // every record counts towards the row of an error, including skipped ones
+        assert_eq!(r.errors()[0].position(), (2, 1));
+        assert_eq!(r.errors()[1].position(), (3, 1));
+        assert_eq!(r.errors()[1].location().line, 3);
+        assert!(r.errors()[1].to_string().contains("expected an object"));
+    }
+
+    #[test]
+    fn test_ndjson_empty_objects() {
+        let r = parse("{}\n{\"a\":{}}\n", false).expect("parse error");
+
+        assert_eq!(r.columns(), 0);
+        assert_eq!(r.rows(), 0);
+
+        // empty objects are empty rows wherever they are
+        let before = parse("{}\n{\"a\":1}\n", false).expect("parse error");
+        let after = parse("{\"a\":1}\n{}\n", false).expect("parse error");
+
+        assert_eq!(before.data(), &make_strvec![ "", "1" ]);
+        assert_eq!(after.data(), &make_strvec![ "1", "" ]);
+        assert_eq!(before.source(0), Some(RecordSource { line: 1, offset: 0 }));
+        assert_eq!(before.source(1), Some(RecordSource { line: 2, offset: 3 }));
+    }
+
+    #[test]
+    fn test_json_records() {
+        let s = "\n[\n  {\"id\": 1, \"tags\": [\"a\", \"b\"], \"user\": {\"name\": \"ann\"}},\n  {\"id\": 2, \"x\": null}\n]\n";
+        let r = parse_json(s, &JsonOptions::default()).expect("parse error");
+
+        assert_eq!(r.header(), &make_strvec![ "id", "tags", "user.name", "x" ]);
+        assert_eq!(r.data(), &make_strvec![ "1", "[\"a\",\"b\"]", "ann", "",
+                                            "2", "", "", "" ]);
+        assert_eq!(r.source(1), Some(RecordSource { line: 4, offset: 63 }));
+
+        let r = parse_json(s, &JsonOptions { flatten: Flatten::All, .. JsonOptions::default() }).expect("parse error");
+        assert_eq!(r.header(), &make_strvec![ "id", "tags.0", "tags.1", "user.name", "x" ]);
+
+        let r = parse_json(s, &JsonOptions { flatten: Flatten::None, .. JsonOptions::default() }).expect("parse error");
+        assert_eq!(r.header(), &make_strvec![ "id", "tags", "user", "x" ]);
+        assert_eq!(r.data()[2], "{\"name\":\"ann\"}");
+    }
+
+    #[test]
+    fn test_json_records_not_objects() {
+        let s = "[{\"a\":1},\n 2,\n \"b\",\n {\"a\":3}]";
+        let e = parse_json(s, &JsonOptions::default()).err().expect("no error");
+
+        assert_eq!(e.position(), (2, 1));
+        assert_eq!(e.location(), &Location { line: 2, offset: 11, column: 2, snippet: " 2,\n ^".to_string() });
+
+        let r = parse_json(s, &JsonOptions { lenient: true, .. JsonOptions::default() }).expect("parse error");
+        assert_eq!(r.data(), &make_strvec![ "1", "3" ]);
+        assert_eq!(r.errors().iter().map(|e| e.position()).collect::<Vec<_>>(), vec![ (2, 1), (3, 1) ]);
+
+        // a syntax error anywhere in the document
+        let e = parse_json("[{\"a\":1},\n {\"a\" 3}]", &JsonOptions::default()).err().expect("no error");
+        assert_eq!((e.location().line, e.location().column), (2, 7));
+    }
+
+    #[test]
+    fn test_json_split() {
+        let s = "{\"columns\":[\"a\",\"b\"],\"index\":[0,1],\"data\":[[1,\"x\"],[2,{\"c\":true}]]}";
+        let r = parse_json(s, &JsonOptions::default()).expect("parse error");
+
+        assert_eq!(r.header(), &make_strvec![ "a", "b", "b.c" ]);
+        assert_eq!(r.data(), &make_strvec![ "1", "x", "", "2", "", "true" ]);
+
+        // rows that are not arrays are numbered by their place in the data
+        let s = "{\"columns\":[\"a\"],\"data\":[1,2,[3]]}";
+        let r = parse_json(s, &JsonOptions { lenient: true, .. JsonOptions::default() }).expect("parse error");
+        assert_eq!(r.data(), &make_strvec![ "3" ]);
+        assert_eq!(r.errors().iter().map(|e| e.position()).collect::<Vec<_>>(), vec![ (1, 1), (2, 1) ]);
+
+        // pretty-printed documents are detected once read in full
+        let s = "{\n  \"columns\": [\"a\", \"b\"],\n  \"data\": [\n    [1, 2],\n    [3]\n  ]\n}";
+        let e = parse_json(s, &JsonOptions::default()).err().expect("no error");
+
+        assert_eq!(e.position(), (2, 2));
+        assert_eq!(e.location().line, 5);
+
+        let r = parse_json(s, &JsonOptions { lenient: true, .. JsonOptions::default() }).expect("parse error");
+        assert_eq!(r.data(), &make_strvec![ "1", "2", "3", "" ]);
+        assert_eq!(r.errors().len(), 1);
+
+        let r = parse_json("{\"columns\":[\"a\"],\"data\":[]}", &JsonOptions::default()).expect("parse error");
+        assert_eq!((r.header(), r.rows()), (&make_strvec![ "a" ], 0));
+    }
+
+    #[test]
+    fn test_json_detect_layout() {
+        let lines = "{\"a\":1}\n{\"a\":2}\n";
+        assert_eq!(parse_json(lines, &JsonOptions::default()).expect("parse error").rows(), 2);
+
+        let record = "{\n  \"a\": 1,\n  \"b\": [1, 2]\n}\n";
+        let r = parse_json(record, &JsonOptions::default()).expect("parse error");
+        assert_eq!(r.data(), &make_strvec![ "1", "[1,2]" ]);
+
+        assert_eq!(detect_layout("[{\"a\":1},"), Some(JsonLayout::Records));
+        assert_eq!(detect_layout("{\"columns\":[],\"data\":[]}"), Some(JsonLayout::Split));
+        assert_eq!(detect_layout("{\"columns\":[]}"), Some(JsonLayout::Lines));
+        assert_eq!(detect_layout("{"), None);
+
+        assert_eq!(parse_json("", &JsonOptions::default()).expect("parse error").rows(), 0);
+    }
+
+    #[test]
+    fn test_is_json_file() {
+        assert!(is_json_file("export.json"));
+        assert!(is_json_file("events.NDJSON.gz"));
+        assert!(is_json_file("bundle.zip!/logs/app.jsonl"));
+        assert!(!is_json_file("data.csv"));
+    }
+}
@@ -0,0 +1,693 @@
+use std::error;
+use std::fmt::{Display, Formatter};
+use std::fmt::Result as FmtResult;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+
+use crate::table::archive::{open_member, split_member_path};
+use crate::table::columnar::{detect_columnar_file, ColumnarTable};
+use crate::table::compression::DecompressReader;
+use crate::table::data::{Table, TableData, TableDataValidationError};
+use crate::table::dialect::Dialect;
+use crate::table::encoding::{DecodeReader, DetectedEncoding, Encoding};
+use crate::table::json::{from_json_reader, is_json_file, JsonOptions};
+use crate::table::mapped::MappedTable;
+use crate::table::markup::{from_markup_reader, MarkupFormat};
+use crate::table::parallel::from_slice_parallel_with;
+use crate::table::reader::{build_table_with, is_stdin, is_stream, Records};
+use crate::table::scan::can_scan;
+use crate::table::sniffer::{sniff_reader, SNIFF_SAMPLE_SIZE};
+use crate::table::sqlite::{from_query_with, is_sqlite_file};
+use crate::table::workbook::{from_sheet, is_workbook_file};
+
+type TableResult<T> = Result<T, TableDataValidationError>;
+
+/// The outcome of a load. Validation errors of a strict parse are kept apart from io
+/// errors so the caller can offer to load the file again leniently
+pub type LoadResult = io::Result<TableResult<Loaded>>;
+
+/// Number of records between progress reports
+const PROGRESS_INTERVAL: usize = 10_000;
+
+/// Encoding reported for workbooks, databases and columnar files, whose cells are read as text already
+const TEXT_ENCODING: DetectedEncoding = DetectedEncoding { encoding: Encoding::Utf8, bom: false };
+
+/// Shared flag to stop a load from another thread. Clones cancel the same load
+#[derive(Debug,Clone,Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Fail with a cancelled error once the token has been cancelled, see is_cancelled
+    pub fn check(&self) -> io::Result<()> {
+        if self.is_cancelled() {
+            Err(io::Error::new(io::ErrorKind::Other, Cancelled))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Error returned by a cancelled load
+#[derive(Debug)]
+struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Loading was cancelled")
+    }
+}
+
+impl error::Error for Cancelled {}
+
+/// Test whether an error is from a cancelled load
+pub fn is_cancelled(error: &io::Error) -> bool {
+    error.get_ref().map_or(false, |e| e.is::<Cancelled>())
+}
+
+/// How far a load has got
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub struct Progress {
+    /// Bytes of the input processed so far
+    pub bytes: u64,
+    /// Size of the input. Decompressed or decoded input differs in size from the file
+    pub total: u64,
+    /// Records processed so far including the header
+    pub rows: usize,
+}
+
+impl Progress {
+    /// Processed part of the input as a percentage
+    pub fn percent(&self) -> u64 {
+        if self.total == 0 { 100 } else { self.bytes.min(self.total) * 100 / self.total }
+    }
+}
+
+/// What to load and how
+#[derive(Debug,Clone)]
+pub struct LoadRequest {
+    pub filename: String,
+    pub dialect: Dialect,
+    /// Encoding of the file or None to detect it
+    pub encoding: Option<Encoding>,
+    /// Smallest UTF-8 file that is memory mapped instead of read into memory, None to never map
+    pub map_size: Option<u64>,
+    /// Sheet to read from a workbook, or table from an HTML or Markdown file, None for the first one
+    pub sheet: Option<String>,
+    /// Query to run against a database, which is required to open one
+    pub query: Option<String>,
+}
+
+/// A loaded table
+pub struct Loaded {
+    pub data: Box<dyn Table>,
+    pub encoding: DetectedEncoding,
+    /// The table is a memory mapped file
+    pub mapped: bool,
+    /// Name of the sheet that was read from a workbook, or of the table read from an HTML or
+    /// Markdown file
+    pub sheet: Option<String>,
+    /// Dialect sniffed while loading a stream, which can not be sniffed before it is read
+    pub dialect: Option<Dialect>,
+}
+
+/// Stops reading once a load is cancelled
+struct CancelReader<'a, R> {
+    reader: R,
+    cancel: &'a CancelToken,
+}
+
+impl<'a, R: Read> Read for CancelReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.cancel.check()?;
+        self.reader.read(buf)
+    }
+}
+
+/// Load a table on the current thread
+///
+/// Uncompressed files of at least the map size are memory mapped if they can be. Other
+/// uncompressed files are decoded into memory and parsed on every available core when
+/// their dialect can be scanned. Anything else, including compressed files and members of
+/// ZIP archives, is parsed as it is decompressed and decoded. Progress is reported every
+/// PROGRESS_INTERVAL records, possibly from several threads at once. A cancelled load stops
+/// with an error for which is_cancelled is true
+///
+/// Files with a JSON extension are read as JSON tables in any layout on the current thread
+/// and only report progress when they start. The dialect only says if they are lenient.
+/// Spreadsheet workbooks are read the same way, with the dialect saying if the first row
+/// is a header, and so are HTML and Markdown files, whose table is named by the sheet of
+/// the request. The query of a request for an SQLite database is run against it, with the
+/// dialect saying if the column names are the header, reporting progress in rows only. Parquet and Arrow IPC files are opened lazily, reading their row
+/// groups or record batches as rows are asked for
+///
+/// Stdin and named pipes are parsed as they are read, see load_stream
+pub fn load<P>(request: &LoadRequest, cancel: &CancelToken, progress: P) -> LoadResult
+    where P: Fn(Progress) + Sync
+{
+    if is_stream(&request.filename) {
+        let input: Box<dyn Read> = if is_stdin(&request.filename) {
+            Box::new(io::stdin())
+        } else {
+            Box::new(File::open(&request.filename)?)
+        };
+
+        return load_stream(input, request, cancel, progress);
+    }
+
+    if is_workbook_file(&request.filename) {
+        progress(Progress::default());
+
+        let (table, sheet) = from_sheet(&request.filename, request.sheet.as_deref(), request.dialect.header)?;
+
+        return Ok(Ok(Loaded {
+            data: Box::new(table), encoding: TEXT_ENCODING, mapped: false, sheet: Some(sheet), dialect: None
+        }));
+    }
+
+    let (path, member) = split_member_path(&request.filename);
+
+    if member.is_none() && is_sqlite_file(path) {
+        let query = request.query.as_deref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "choose a table or a query to open a database"))?;
+        let mut rows = 0;
+
+        progress(Progress::default());
+
+        let table = from_query_with(path, query, request.dialect.header, || {
+            cancel.check()?;
+            rows += 1;
+
+            if rows % PROGRESS_INTERVAL == 0 {
+                progress(Progress { rows, .. Progress::default() });
+            }
+
+            Ok(())
+        })?;
+
+        return Ok(Ok(Loaded {
+            data: Box::new(table), encoding: TEXT_ENCODING, mapped: false, sheet: None, dialect: None
+        }));
+    }
+
+    if member.is_none() && detect_columnar_file(path).is_some() {
+        progress(Progress::default());
+
+        let table = ColumnarTable::open(path)?;
+
+        return Ok(Ok(Loaded {
+            data: Box::new(table), encoding: TEXT_ENCODING, mapped: false, sheet: None, dialect: None
+        }));
+    }
+
+    let size = fs::metadata(path)?.len();
+    let utf8 = request.encoding.map_or(true, |e| e == Encoding::Utf8);
+    let json = is_json_file(&request.filename);
+    let markup = MarkupFormat::from_filename(&request.filename);
+
+    if member.is_none() && utf8 && !json && markup.is_none() && request.map_size.map_or(false, |m| size >= m) {
+        let mut rows = 0;
+        let mut bytes = 0;
+
+        let mapped = MappedTable::open_with(&request.filename, &request.dialect, |n| {
+            cancel.check()?;
+            rows += 1;
+            bytes += n as u64;
+
+            if rows % PROGRESS_INTERVAL == 0 {
+                progress(Progress { bytes, total: size, rows });
+            }
+
+            Ok(())
+        });
+
+        match mapped {
+            Ok(Ok(table)) => return Ok(Ok(Loaded {
+                encoding: table.encoding(), data: Box::new(table), mapped: true, sheet: None, dialect: None
+            })),
+            Ok(Err(e)) => return Ok(Err(e)),
+            Err(e) if is_cancelled(&e) => return Err(e),
+            // files that can not be mapped are read into memory instead
+            Err(_) => {}
+        }
+    }
+
+    let input: Box<dyn Read> = match member {
+        Some(member) => Box::new(open_member(path, member)?),
+        None => Box::new(CancelReader { reader: File::open(path)?, cancel })
+    };
+    let decompressor = DecompressReader::new(input)?;
+    let plain = member.is_none() && decompressor.compression().is_none();
+    let mut decoder = DecodeReader::new(decompressor, request.encoding)?;
+
+    if json {
+        progress(Progress { bytes: 0, total: size, rows: 0 });
+
+        let options = JsonOptions { lenient: request.dialect.lenient, .. JsonOptions::default() };
+        let table = from_json_reader(BufReader::new(&mut decoder), &options)?;
+        let encoding = decoder.encoding();
+
+        return Ok(table.map(|t| Loaded { data: Box::new(t), encoding, mapped: false, sheet: None, dialect: None }));
+    }
+
+    if let Some(format) = markup {
+        progress(Progress { bytes: 0, total: size, rows: 0 });
+
+        let (table, name) = from_markup_reader(&mut decoder, format, request.sheet.as_deref())?;
+        let encoding = decoder.encoding();
+
+        return Ok(Ok(Loaded { data: Box::new(table), encoding, mapped: false, sheet: Some(name), dialect: None }));
+    }
+
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+
+    if !plain || threads == 1 || !can_scan(&request.dialect) {
+        // the size of decompressed input is not known
+        let total = if plain { size } else { 0 };
+        let table = parse_records(BufReader::new(&mut decoder), &request.dialect, total, cancel, &progress)?;
+        let encoding = decoder.encoding();
+
+        return Ok(table.map(|t| Loaded { data: Box::new(t), encoding, mapped: false, sheet: None, dialect: None }));
+    }
+
+    let mut data = Vec::with_capacity(size as usize);
+    decoder.read_to_end(&mut data)?;
+    let encoding = decoder.encoding();
+
+    let total = data.len() as u64;
+    let rows = AtomicUsize::new(0);
+    let bytes = AtomicU64::new(0);
+
+    progress(Progress { bytes: 0, total, rows: 0 });
+
+    let on_record = |n: usize| {
+        cancel.check()?;
+        let rows = rows.fetch_add(1, Ordering::Relaxed) + 1;
+        let bytes = bytes.fetch_add(n as u64, Ordering::Relaxed) + n as u64;
+
+        if rows % PROGRESS_INTERVAL == 0 {
+            progress(Progress { bytes, total, rows });
+        }
+
+        Ok(())
+    };
+
+    let table = from_slice_parallel_with(&data, &request.dialect, threads, &on_record)?;
+
+    Ok(table.map(|t| Loaded { data: Box::new(t), encoding, mapped: false, sheet: None, dialect: None }))
+}
+
+/// Load delimited data from input that can only be read once, parsing it as it is read
+///
+/// The dialect is sniffed from the start of the input, keeping the lenient choice and the
+/// comment lines of the request, and falls back to the dialect of the request. A sniffed
+/// preamble is skipped unless the request has preamble options, which are kept instead.
+/// The size of the input is not known so progress is reported in rows only
+fn load_stream<R, P>(input: R, request: &LoadRequest, cancel: &CancelToken, progress: P) -> LoadResult
+    where R: Read,
+          P: Fn(Progress)
+{
+    let mut decoder = DecodeReader::new(DecompressReader::new(CancelReader { reader: input, cancel })?, request.encoding)?;
+
+    progress(Progress::default());
+
+    let (sniffed, input) = sniff_reader(BufReader::new(&mut decoder), SNIFF_SAMPLE_SIZE)?;
+    let dialect = if sniffed.confidence > 0.0 {
+        // the input can only be read once so there is no asking before the preamble is skipped
+        let base = sniffed.skip_preamble.unwrap_or(sniffed.dialect);
+        let (skip_lines, skip_until) = if request.dialect.has_preamble() {
+            (request.dialect.skip_lines, request.dialect.skip_until.clone())
+        } else {
+            (base.skip_lines, None)
+        };
+
+        Dialect {
+            lenient: request.dialect.lenient,
+            comment: request.dialect.comment.clone(),
+            skip_lines,
+            skip_until,
+            .. base
+        }
+    } else {
+        request.dialect.clone()
+    };
+
+    let table = parse_records(input, &dialect, 0, cancel, &progress)?;
+    let encoding = decoder.encoding();
+
+    Ok(table.map(|t| Loaded { data: Box::new(t), encoding, mapped: false, sheet: None, dialect: Some(dialect) }))
+}
+
+/// Parse delimited data as it is read on the current thread, reporting progress towards
+/// a total size in bytes or in rows only if the total is 0
+fn parse_records<R, P>(input: R, dialect: &Dialect, total: u64, cancel: &CancelToken, progress: &P)
+    -> io::Result<TableResult<TableData>>
+    where R: BufRead,
+          P: Fn(Progress)
+{
+    let mut rows = 0;
+    let mut bytes = 0;
+
+    progress(Progress { bytes: 0, total, rows: 0 });
+
+    build_table_with(Records::new(input, dialect), dialect, |n| {
+        cancel.check()?;
+        rows += 1;
+        bytes += n as u64;
+
+        if rows % PROGRESS_INTERVAL == 0 {
+            progress(Progress { bytes, total, rows });
+        }
+
+        Ok(())
+    })
+}
+
+/// Loads a table on a worker thread
+///
+/// Progress is sent over a channel and read with progress(). The notify callback is
+/// called from the worker after every progress report and once more when the load has
+/// finished, for example to wake up a GUI thread
+pub struct Loader {
+    request: LoadRequest,
+    progress: Receiver<Progress>,
+    cancel: CancelToken,
+    handle: JoinHandle<LoadResult>,
+}
+
+impl Loader {
+    pub fn spawn<F>(request: LoadRequest, notify: F) -> Self
+        where F: Fn() + Send + Sync + 'static
+    {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = CancelToken::new();
+
+        let worker_request = request.clone();
+        let worker_cancel = cancel.clone();
+
+        let handle = thread::spawn(move || {
+            let sender = Mutex::new(sender);
+
+            let result = load(&worker_request, &worker_cancel, |p| {
+                // a loader that has been dropped no longer wants progress
+                let _ = sender.lock().unwrap().send(p);
+                notify();
+            });
+
+            notify();
+            result
+        });
+
+        Loader { request, progress: receiver, cancel, handle }
+    }
+
+    pub fn request(&self) -> &LoadRequest {
+        &self.request
+    }
+
+    /// Ask the worker to stop. wait() then returns a cancelled error unless it had already finished
+    pub fn cancel(&self) {
+        self.cancel.cancel()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// The latest progress reported since the last call
+    pub fn progress(&self) -> Option<Progress> {
+        self.progress.try_iter().last()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Wait for the worker to finish and take its result
+    pub fn wait(self) -> LoadResult {
+        self.handle.join()
+            .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Other, "the loader thread panicked")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+
+    use super::*;
+    use crate::table::reader::STDIN_FILENAME;
+
+    fn request(filename: &str, map_size: Option<u64>) -> LoadRequest {
+        LoadRequest {
+            filename: filename.to_owned(),
+            dialect: Dialect { header: true, .. Dialect::csv() },
+            encoding: None,
+            map_size,
+            sheet: None,
+            query: None,
+        }
+    }
+
+    fn rows(count: usize) -> String {
+        let mut s = "Name,Value\n".to_owned();
+
+        for i in 0..count {
+            s.push_str(&format!("value{},{}\n", i, i));
+        }
+
+        s
+    }
+
+    #[test]
+    fn test_load_progress() {
+        let filename = "loader_progress.csv";
+        let s = rows(PROGRESS_INTERVAL * 2);
+        fs::write(filename, &s).expect("write failed");
+
+        for map_size in [ None, Some(0) ] {
+            let reports = Mutex::new(Vec::new());
+            let r = load(&request(filename, map_size), &CancelToken::new(), |p| reports.lock().unwrap().push(p));
+            let loaded = r.expect("read error").expect("invalid data");
+
+            assert_eq!(loaded.mapped, map_size.is_some());
+            assert_eq!(loaded.data.rows(), PROGRESS_INTERVAL * 2);
+            assert_eq!(loaded.data.row(1), Some(vec![ "value1".to_owned(), "1".to_owned() ]));
+
+            let reports = reports.into_inner().unwrap();
+            let last = reports.last().expect("no progress");
+            assert_eq!(last.rows, PROGRESS_INTERVAL * 2);
+            assert_eq!(last.total, s.len() as u64);
+            assert!(last.bytes < last.total);
+        }
+
+        fs::remove_file(filename).expect("remove failed");
+    }
+
+    #[test]
+    fn test_load_compressed_progress() {
+        let filename = "loader_progress.csv.gz";
+        let mut gz = GzEncoder::new(File::create(filename).expect("create failed"), flate2::Compression::default());
+        gz.write_all(rows(PROGRESS_INTERVAL * 2).as_bytes()).expect("write failed");
+        gz.finish().expect("write failed");
+
+        let reports = Mutex::new(Vec::new());
+        let r = load(&request(filename, Some(0)), &CancelToken::new(), |p| reports.lock().unwrap().push(p));
+        fs::remove_file(filename).expect("remove failed");
+        let loaded = r.expect("read error").expect("invalid data");
+
+        assert_eq!(loaded.mapped, false);
+        assert_eq!(loaded.data.rows(), PROGRESS_INTERVAL * 2);
+
+        // compressed input is parsed as it is decompressed, so only rows are counted
+        let reports = reports.into_inner().unwrap();
+        assert_eq!(reports.last().map(|p| (p.rows, p.total)), Some((PROGRESS_INTERVAL * 2, 0)));
+    }
+
+    #[test]
+    fn test_load_cancelled() {
+        let filename = "loader_cancelled.csv";
+        fs::write(filename, rows(PROGRESS_INTERVAL * 3)).expect("write failed");
+
+        for map_size in [ None, Some(0) ] {
+            let cancel = CancelToken::new();
+            let r = load(&request(filename, map_size), &cancel, |p| if p.rows > 0 { cancel.cancel() });
+
+            assert!(is_cancelled(&r.err().expect("not cancelled")));
+        }
+
+        fs::remove_file(filename).expect("remove failed");
+    }
+
+    #[test]
+    fn test_loader_thread() {
+        let filename = "loader_thread.csv";
+        fs::write(filename, rows(PROGRESS_INTERVAL)).expect("write failed");
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let loader = Loader::spawn(request(filename, None), move || { let _ = sender.lock().unwrap().send(()); });
+
+        // notified at least once for progress and once when finished
+        receiver.recv().expect("not notified");
+        receiver.recv().expect("not notified");
+
+        let r = loader.wait();
+        fs::remove_file(filename).expect("remove failed");
+
+        assert_eq!(r.expect("read error").expect("invalid data").data.rows(), PROGRESS_INTERVAL);
+    }
+
+    #[test]
+    fn test_load_strict_error() {
+        let filename = "loader_strict.csv";
+        fs::write(filename, "a,b\nc\n").expect("write failed");
+
+        let r = load(&request(filename, None), &CancelToken::new(), |_| ());
+        fs::remove_file(filename).expect("remove failed");
+
+        assert!(matches!(r, Ok(Err(TableDataValidationError::RowFieldCountMismatchError { .. }))));
+    }
+
+    #[test]
+    fn test_load_json() {
+        let filename = "loader_records.json";
+        fs::write(filename, "[{\"Name\":\"value1\",\"Value\":10},\n 5]").expect("write failed");
+
+        let r = load(&request(filename, Some(0)), &CancelToken::new(), |_| ());
+        assert!(matches!(r, Ok(Err(TableDataValidationError::JsonError { row: 2, .. }))));
+
+        let mut lenient = request(filename, Some(0));
+        lenient.dialect.lenient = true;
+        let loaded = load(&lenient, &CancelToken::new(), |_| ()).expect("read error").expect("invalid data");
+        fs::remove_file(filename).expect("remove failed");
+
+        assert_eq!(loaded.mapped, false);
+        assert_eq!(loaded.data.row(0), Some(vec![ "value1".to_owned(), "10".to_owned() ]));
+    }
+
+    #[test]
+    fn test_load_stream() {
+        let input: &[u8] = b"Name;Value\nvalue1;10\nvalue2;20\n";
+
+        let mut stdin = request(STDIN_FILENAME, None);
+        stdin.dialect.lenient = true;
+        let loaded = load_stream(input, &stdin, &CancelToken::new(), |_| ())
+            .expect("read error").expect("invalid data");
+
+        // the dialect is sniffed as the input is read
+        let dialect = loaded.dialect.expect("no dialect");
+        assert_eq!((dialect.delimiter, dialect.header, dialect.lenient), (';', true, true));
+        assert_eq!(loaded.data.header(), &[ "Name".to_owned(), "Value".to_owned() ]);
+        assert_eq!(loaded.data.row(1), Some(vec![ "value2".to_owned(), "20".to_owned() ]));
+
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let r = load_stream(input, &stdin, &cancel, |_| ());
+        assert!(r.err().map_or(false, |e| is_cancelled(&e)));
+    }
+
+    #[test]
+    fn test_load_stream_preamble() {
+        let input: &[u8] = b"Bank export\nAccount: 1234\n\nName;Value\nvalue1;10\nvalue2;20\n";
+        let loaded = load_stream(input, &request(STDIN_FILENAME, None), &CancelToken::new(), |_| ())
+            .expect("read error").expect("invalid data");
+
+        // the sniffed preamble is kept out of the table
+        assert_eq!(loaded.dialect.map(|d| d.skip_lines), Some(3));
+        assert_eq!(loaded.data.preamble(), &[ "Bank export".to_owned(), "Account: 1234".to_owned(), "".to_owned() ]);
+        assert_eq!(loaded.data.header(), &[ "Name".to_owned(), "Value".to_owned() ]);
+        assert_eq!(loaded.data.rows(), 2);
+    }
+
+    #[test]
+    fn test_load_mapped_strict() {
+        let filename = "loader_mapped_strict.csv";
+        fs::write(filename, "a,b\nc\n").expect("write failed");
+
+        let mut strict = request(filename, Some(0));
+        strict.dialect.header = false;
+        let r = load(&strict, &CancelToken::new(), |_| ()).expect("read error");
+
+        let mut lenient = strict.clone();
+        lenient.dialect.lenient = true;
+        let loaded = load(&lenient, &CancelToken::new(), |_| ()).expect("read error").expect("invalid data");
+        fs::remove_file(filename).expect("remove failed");
+
+        // a mapped file is checked as strictly as one read into memory
+        assert!(matches!(r.err(), Some(TableDataValidationError::RowFieldCountMismatchError { .. })));
+        assert_eq!(loaded.mapped, true);
+        assert_eq!(loaded.data.errors().len(), 1);
+    }
+
+    #[test]
+    fn test_load_preamble() {
+        let filename = "loader_preamble.csv";
+        fs::write(filename, format!("Exported by a \"logger\nSerial: 42\n{}", rows(3))).expect("write failed");
+
+        // a file with a preamble is never mapped
+        let mut preamble = request(filename, Some(0));
+        preamble.dialect.skip_until = Some("^Name,".to_owned());
+        let loaded = load(&preamble, &CancelToken::new(), |_| ()).expect("read error").expect("invalid data");
+        fs::remove_file(filename).expect("remove failed");
+
+        assert_eq!(loaded.mapped, false);
+        assert_eq!(loaded.data.preamble(), &[ "Exported by a \"logger".to_owned(), "Serial: 42".to_owned() ]);
+        assert_eq!(loaded.data.header(), &[ "Name".to_owned(), "Value".to_owned() ]);
+        assert_eq!(loaded.data.rows(), 3);
+    }
+
+    #[test]
+    fn test_load_markdown() {
+        let filename = "loader_tables.md";
+        fs::write(filename, "# First\n\n| a |\n|---|\n| 1 |\n\n# Second\n\n| b |\n|---|\n| 2 |\n")
+            .expect("write failed");
+
+        // the whole file is read even when it is large enough to map
+        let mut second = request(filename, Some(0));
+        second.sheet = Some("Second".to_owned());
+        let loaded = load(&second, &CancelToken::new(), |_| ()).expect("read error").expect("invalid data");
+        fs::remove_file(filename).expect("remove failed");
+
+        assert_eq!(loaded.mapped, false);
+        assert_eq!(loaded.sheet.as_deref(), Some("Second"));
+        assert_eq!(loaded.data.header(), &[ "b".to_owned() ]);
+        assert_eq!(loaded.data.row(0), Some(vec![ "2".to_owned() ]));
+    }
+
+    #[test]
+    fn test_load_sqlite() {
+        let filename = "loader_query.db";
+        let _ = fs::remove_file(filename);
+        let connection = rusqlite::Connection::open(filename).expect("create failed");
+        connection.execute_batch("CREATE TABLE t (Name TEXT, Value INTEGER); INSERT INTO t VALUES ('value1', 10);")
+            .expect("setup failed");
+        drop(connection);
+
+        let mut query = request(filename, Some(0));
+        let r = load(&query, &CancelToken::new(), |_| ());
+        assert_eq!(r.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+
+        query.query = Some("SELECT * FROM t".to_owned());
+        let loaded = load(&query, &CancelToken::new(), |_| ()).expect("read error").expect("invalid data");
+        fs::remove_file(filename).expect("remove failed");
+
+        assert_eq!(loaded.data.row(0), Some(vec![ "value1".to_owned(), "10".to_owned() ]));
+    }
+}
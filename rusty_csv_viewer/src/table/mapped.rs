@@ -0,0 +1,396 @@
+use std::borrow::Cow;
+use std::cmp;
+use std::fs::File;
+use std::io;
+
+use memmap2::Mmap;
+
+use crate::table::compression::detect_compression;
+use crate::table::data::{Location, RecordSource, Table, TableDataValidationError};
+use crate::table::dialect::Dialect;
+use crate::table::encoding::{detect_encoding, DetectedEncoding, Encoding, DETECT_SAMPLE_SIZE};
+use crate::table::reader::{snippet, Records};
+use crate::table::scan::{can_scan, RecordStart, RecordStarts};
+
+type TableResult<T> = Result<T, TableDataValidationError>;
+
+/// Number of records between stored line numbers. The line of any other record is
+/// counted from the nearest stored line before it
+const LINE_MARK_INTERVAL: usize = 1024;
+
+/// A table backed by a memory-mapped file
+///
+/// Opening the file indexes where every record starts in a single pass over its bytes,
+/// counting the fields of every record to check them as strictly as the dialect asks.
+/// Rows are only parsed when they are requested so files larger than the available
+/// memory can be viewed. Quoting follows the lenient parser so malformed rows are
+/// still shown, padded to the width of the widest record.
+///
+/// Only UTF-8 files can be mapped as the index works on raw bytes
+pub struct MappedTable {
+    map: Mmap,
+    /// Dialect used to parse a single row
+    dialect: Dialect,
+    encoding: DetectedEncoding,
+    /// Length of the byte order mark which offsets in the decoded input do not include
+    start: usize,
+    header: Vec<String>,
+    columns: usize,
+    /// Problems that were skipped over when the file was indexed leniently
+    errors: Vec<TableDataValidationError>,
+    /// Start of every record including the header, followed by the end of the input
+    offsets: Vec<usize>,
+    /// Line of every LINE_MARK_INTERVAL-th record
+    line_marks: Vec<usize>,
+    /// Index of the first data record in offsets
+    first: usize,
+}
+
+impl MappedTable {
+    /// Map a file and index its records
+    ///
+    /// Returns the first record with a different number of fields to the first record
+    /// unless the dialect is lenient. Quotes are not checked.
+    /// Fails with InvalidData if the file is compressed or not UTF-8 and InvalidInput if the delimiter,
+    /// quote, escape or terminator of the dialect is not an ASCII character, the fields are not
+    /// separated by the delimiter or lines are skipped as a preamble
+    pub fn open(filename: &str, dialect: &Dialect) -> io::Result<TableResult<Self>> {
+        MappedTable::open_with(filename, dialect, |_| Ok(()))
+    }
+
+    /// Map a file and index its records like open, calling back after every record with
+    /// the number of bytes it takes up. An error from the callback stops the indexing
+    pub fn open_with<F>(filename: &str, dialect: &Dialect, on_record: F) -> io::Result<TableResult<Self>>
+        where F: FnMut(usize) -> io::Result<()>
+    {
+        if !can_scan(dialect) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "only dialects of ASCII characters and a delimiter without a preamble can be memory mapped"));
+        }
+
+        let file = File::open(filename)?;
+
+        if file.metadata()?.len() == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "an empty file can not be memory mapped"));
+        }
+
+        // Safety: the map is only read from. The file could still be changed by another
+        // process while it is mapped which is the same risk every viewer of large files takes
+        let map = unsafe { Mmap::map(&file)? };
+        if let Some(compression) = detect_compression(&map) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("{} compressed files can not be memory mapped", compression)));
+        }
+
+        let encoding = detect_encoding(&map[..cmp::min(map.len(), DETECT_SAMPLE_SIZE)]);
+
+        if encoding.encoding != Encoding::Utf8 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("{} files can not be memory mapped", encoding.encoding)));
+        }
+
+        let start = if encoding.bom { encoding.encoding.bom().len() } else { 0 };
+        // records are split and parsed leniently so a row is parsed as it was indexed
+        let row_dialect = Dialect { header: false, lenient: true, .. dialect.clone() };
+        let index = match index_records(&map, start, &row_dialect, dialect, on_record)? {
+            Ok(index) => index,
+            Err(e) => return Ok(Err(e))
+        };
+
+        let mut table = MappedTable {
+            map,
+            dialect: row_dialect,
+            encoding,
+            start,
+            header: Vec::new(),
+            columns: index.columns,
+            errors: index.errors,
+            offsets: index.offsets,
+            line_marks: index.line_marks,
+            first: 0,
+        };
+
+        if dialect.header && table.offsets.len() > 1 {
+            table.header = table.parse(0);
+            table.header.resize(table.columns, String::new());
+            table.first = 1;
+        }
+
+        Ok(Ok(table))
+    }
+
+    /// The encoding of the mapped file
+    pub fn encoding(&self) -> DetectedEncoding {
+        self.encoding
+    }
+
+    /// Parse the record at an index of offsets
+    fn parse(&self, record: usize) -> Vec<String> {
+        let bytes = &self.map[self.offsets[record]..self.offsets[record+1]];
+
+        match Records::new(bytes, &self.dialect).next() {
+            Some(Ok(fields)) => fields,
+            // a record that is not valid UTF-8 is parsed again with replacement characters
+            Some(Err(_)) => {
+                let text = String::from_utf8_lossy(bytes);
+                Records::new(text.as_bytes(), &self.dialect).next()
+                    .and_then(|r| r.ok())
+                    .unwrap_or_default()
+            },
+            None => Vec::new()
+        }
+    }
+}
+
+impl Table for MappedTable {
+    fn header(&self) -> &[String] {
+        &self.header
+    }
+
+    fn columns(&self) -> usize {
+        self.columns
+    }
+
+    fn rows(&self) -> usize {
+        self.offsets.len().saturating_sub(self.first + 1)
+    }
+
+    fn row(&self, row: usize) -> Option<Vec<String>> {
+        if row >= self.rows() {
+            return None;
+        }
+
+        let mut fields = self.parse(row + self.first);
+
+        if fields.len() < self.columns {
+            fields.resize(self.columns, String::new());
+        }
+
+        Some(fields)
+    }
+
+    fn source(&self, row: usize) -> Option<RecordSource> {
+        if row >= self.rows() {
+            return None;
+        }
+
+        let record = row + self.first;
+        let mark = record / LINE_MARK_INTERVAL;
+        let from = self.offsets[mark * LINE_MARK_INTERVAL];
+        let line = self.line_marks[mark] + count_lines(&self.map[from..self.offsets[record]]);
+
+        Some(RecordSource { line, offset: self.offsets[record] - self.start })
+    }
+
+    fn errors(&self) -> Cow<'_, [TableDataValidationError]> {
+        Cow::Borrowed(&self.errors)
+    }
+}
+
+/// Where the records of a file start and what was found checking them
+struct RecordIndex {
+    /// Start of every record followed by the end of the input
+    offsets: Vec<usize>,
+    /// Line of every LINE_MARK_INTERVAL-th record
+    line_marks: Vec<usize>,
+    /// Most fields of any record
+    columns: usize,
+    errors: Vec<TableDataValidationError>,
+}
+
+/// Find where every record starts in one pass over the bytes of the input
+///
+/// The records are split by the scan dialect and their field counts checked against the
+/// first record as the check dialect asks. Returns the first mismatch unless it is
+/// lenient. The callback gets the bytes from the previous record start to each record
+/// start and once more for the last record
+fn index_records<F>(data: &[u8], start: usize, scan: &Dialect, check: &Dialect, mut on_record: F)
+    -> io::Result<TableResult<RecordIndex>>
+    where F: FnMut(usize) -> io::Result<()>
+{
+    let mut index = RecordIndex { offsets: Vec::new(), line_marks: Vec::new(), columns: 0, errors: Vec::new() };
+    let mut records = RecordStarts::new(data, start, scan);
+    let mut last: Option<RecordStart> = None;
+    let mut expected = None;
+
+    loop {
+        let record = records.next();
+
+        if let Some(prev) = last {
+            let end = record.map_or(data.len(), |r| r.offset);
+            on_record(end - prev.offset)?;
+
+            let found = records.fields();
+
+            match expected {
+                None => expected = Some(found),
+                Some(expected) if found != expected => {
+                    let error = TableDataValidationError::RowFieldCountMismatchError {
+                        row: index.offsets.len() - check.header as usize, expected, found,
+                        location: locate(data, start, prev, end)
+                    };
+
+                    if !check.lenient {
+                        return Ok(Err(error));
+                    }
+
+                    index.errors.push(error);
+                },
+                _ => ()
+            }
+
+            index.columns = cmp::max(index.columns, found);
+        }
+
+        match record {
+            Some(r) => {
+                if index.offsets.len() % LINE_MARK_INTERVAL == 0 {
+                    index.line_marks.push(r.line);
+                }
+
+                index.offsets.push(r.offset);
+                last = Some(r);
+            },
+            None => break
+        }
+    }
+
+    index.offsets.push(data.len());
+
+    Ok(Ok(index))
+}
+
+/// Locate the start of a record that ends at a byte offset
+fn locate(data: &[u8], start: usize, record: RecordStart, end: usize) -> Location {
+    let bytes = &data[record.offset..end];
+    let line_end = bytes.iter().position(|b| *b == b'\n' || *b == b'\r').unwrap_or(bytes.len());
+
+    Location {
+        line: record.line,
+        offset: record.offset - start,
+        column: 1,
+        snippet: snippet(&String::from_utf8_lossy(&bytes[..line_end]), 0)
+    }
+}
+
+/// Count the line breaks in some bytes treating a '\r\n' as one
+fn count_lines(bytes: &[u8]) -> usize {
+    bytes.iter().enumerate()
+        .filter(|(i, b)| **b == b'\r' || (**b == b'\n' && (*i == 0 || bytes[i-1] != b'\r')))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! make_strvec {
+        [ $($a:expr),+ ] => {
+            vec![ $($a.to_owned()),+ ]
+        }
+    }
+
+    fn setup_mapped(target: &str, data: &[u8], dialect: &Dialect) -> io::Result<TableResult<MappedTable>> {
+        std::fs::write(target, data)?;
+        let table = MappedTable::open(target, dialect);
+        std::fs::remove_file(target)?;
+        table
+    }
+
+    #[test]
+    fn test_index_records() {
+        let s = b"a,b\r\n\r\n\"c\r\nd\",e\r\n# note\nf,\"g\"\"h\"\n";
+        let d = Dialect { comment: Some("#".to_owned()), .. Dialect::csv() };
+        let mut bytes = Vec::new();
+        let index = index_records(s, 0, &d, &d, |n| { bytes.push(n); Ok(()) }).unwrap().unwrap();
+
+        assert_eq!(index.offsets, vec![ 0, 7, 24, s.len() ]);
+        assert_eq!(index.line_marks, vec![ 1 ]);
+        assert_eq!(index.columns, 2);
+        assert_eq!(bytes, vec![ 7, 17, s.len() - 24 ]);
+    }
+
+    #[test]
+    fn test_index_records_stray_quote() {
+        let s = b"a,5\" screen\nb,c\n";
+        let d = Dialect { lenient: true, .. Dialect::csv() };
+        let index = index_records(s, 0, &d, &Dialect::csv(), |_| Ok(())).unwrap().unwrap();
+
+        assert_eq!(index.offsets, vec![ 0, 12, s.len() ]);
+        assert_eq!(index.errors.len(), 0);
+    }
+
+    #[test]
+    fn test_index_records_field_counts() {
+        let s = b"a,b\nc\nd,e,f\n";
+        let d = Dialect { lenient: true, .. Dialect::csv() };
+        let index = index_records(s, 0, &d, &d, |_| Ok(())).unwrap().unwrap();
+
+        assert_eq!(index.columns, 3);
+        assert_eq!(index.errors.iter().map(|e| e.position()).collect::<Vec<_>>(), vec![ (2, 2), (3, 3) ]);
+
+        let r = index_records(s, 0, &d, &Dialect::csv(), |_| Ok(())).unwrap();
+        assert_eq!(r.err().map(|e| e.location().clone()),
+                   Some(Location { line: 2, offset: 4, column: 1, snippet: "c\n^".to_owned() }));
+    }
+
+    #[test]
+    fn test_count_lines() {
+        assert_eq!(count_lines(b"a\r\nb\nc\rd"), 3);
+        assert_eq!(count_lines(b""), 0);
+    }
+
+    #[test]
+    fn test_mapped_table_rows() {
+        let s = "\u{feff}Name,Value\nvalue1,\"multi\nline\"\nvalue2\nvalue3,30\n";
+        let d = Dialect { header: true, lenient: true, .. Dialect::csv() };
+        let t = setup_mapped("mapped_rows.csv", s.as_bytes(), &d).expect("map failed").expect("invalid data");
+
+        assert_eq!(t.header(), &make_strvec![ "Name", "Value" ][..]);
+        assert_eq!(t.rows(), 3);
+        assert_eq!(t.row(0), Some(make_strvec![ "value1", "multi\nline" ]));
+        // short rows are padded
+        assert_eq!(t.row(1), Some(make_strvec![ "value2", "" ]));
+        assert_eq!(t.row(3), None);
+        assert_eq!(t.window(2..4), vec![ make_strvec![ "value3", "30" ] ]);
+        assert_eq!(t.source(2), Some(RecordSource { line: 5, offset: 38 }));
+        assert_eq!(t.errors().len(), 1);
+    }
+
+    #[test]
+    fn test_mapped_table_follows_lenient() {
+        let s = b"Name,Value\nvalue1\nvalue2,20,extra\n";
+        let d = Dialect { header: true, .. Dialect::csv() };
+        let r = setup_mapped("mapped_strict.csv", s, &d).expect("map failed");
+
+        assert!(matches!(r.err(), Some(TableDataValidationError::RowFieldCountMismatchError { row: 1, .. })));
+
+        let d = Dialect { lenient: true, .. d };
+        let t = setup_mapped("mapped_lenient.csv", s, &d).expect("map failed").expect("invalid data");
+
+        // the table is as wide as its widest row like a table read into memory
+        assert_eq!(t.columns(), 3);
+        assert_eq!(t.header(), &make_strvec![ "Name", "Value", "" ][..]);
+        assert_eq!(t.row(0), Some(make_strvec![ "value1", "", "" ]));
+        assert_eq!(t.row(1), Some(make_strvec![ "value2", "20", "extra" ]));
+        assert_eq!(t.errors().len(), 2);
+    }
+
+    #[test]
+    fn test_mapped_table_line_marks() {
+        let s = "a,b\n".repeat(LINE_MARK_INTERVAL * 2 + 10);
+        let t = setup_mapped("mapped_marks.csv", s.as_bytes(), &Dialect::csv()).expect("map failed").expect("invalid data");
+
+        assert_eq!(t.rows(), LINE_MARK_INTERVAL * 2 + 10);
+        assert_eq!(t.source(LINE_MARK_INTERVAL * 2 + 5),
+                   Some(RecordSource { line: LINE_MARK_INTERVAL * 2 + 6, offset: (LINE_MARK_INTERVAL * 2 + 5) * 4 }));
+    }
+
+    #[test]
+    fn test_mapped_table_rejects_other_encodings() {
+        let s = b"\xff\xfea\x00,\x00b\x00";
+        let r = setup_mapped("mapped_utf16.csv", s, &Dialect::csv());
+
+        assert_eq!(r.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,873 @@
+use std::fmt::{Display, Formatter};
+use std::fmt::Result as FmtResult;
+use std::io::{self, Read};
+
+use crate::table::compression::strip_compression_extension;
+use crate::table::data::{RecordSource, TableData};
+use crate::table::encoding::DecodeReader;
+use crate::table::reader::open_file;
+
+/// Extensions of HTML pages
+pub const HTML_EXTENSIONS: [&str; 2] = [".html", ".htm"];
+
+/// Extensions of Markdown documents
+pub const MARKDOWN_EXTENSIONS: [&str; 2] = [".md", ".markdown"];
+
+/// Largest colspan and rowspan that are honoured, as browsers do
+const MAX_COLSPAN: usize = 1000;
+const MAX_ROWSPAN: usize = 65_534;
+
+/// Formats of documents that hold tables among other content
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum MarkupFormat {
+    /// `<table>` elements of an HTML page
+    Html,
+    /// GitHub-flavoured Markdown pipe tables
+    Markdown,
+}
+
+impl MarkupFormat {
+    /// The format of a file from its extension, ignoring a compression extension
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        let filename = strip_compression_extension(filename).to_lowercase();
+
+        if HTML_EXTENSIONS.iter().any(|e| filename.ends_with(e)) {
+            Some(MarkupFormat::Html)
+        } else if MARKDOWN_EXTENSIONS.iter().any(|e| filename.ends_with(e)) {
+            Some(MarkupFormat::Markdown)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for MarkupFormat {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            MarkupFormat::Html => write!(f, "HTML"),
+            MarkupFormat::Markdown => write!(f, "Markdown"),
+        }
+    }
+}
+
+/// A table found in a document
+#[derive(Debug)]
+pub struct MarkupTable {
+    /// Name to choose the table by, unique within its document
+    pub name: String,
+    pub table: TableData,
+}
+
+/// Test whether a file is an HTML or Markdown document from its extension
+pub fn is_markup_file(filename: &str) -> bool {
+    MarkupFormat::from_filename(filename).is_some()
+}
+
+/// List the names of the tables of an HTML or Markdown file in the order they appear in it
+pub fn list_markup_tables(filename: &str) -> io::Result<Vec<String>> {
+    let format = markup_format(filename)?;
+    let mut text = String::new();
+    DecodeReader::new(open_file(filename)?, None)?.read_to_string(&mut text)?;
+
+    Ok(markup_tables(&text, format).into_iter().map(|t| t.name).collect())
+}
+
+/// Read a table of an HTML or Markdown file, or its first table if None
+///
+/// Returns the name of the table that was read alongside it, see from_markup_reader
+pub fn from_markup_file(filename: &str, table: Option<&str>) -> io::Result<(TableData, String)> {
+    let format = markup_format(filename)?;
+    from_markup_reader(DecodeReader::new(open_file(filename)?, None)?, format, table)
+}
+
+/// Read a table of a document, or its first table if None
+///
+/// Fails with InvalidData if the document has no tables and NotFound if none of them has
+/// the name. The source of a row is where it starts in the document
+pub fn from_markup_reader<R: Read>(mut reader: R, format: MarkupFormat, table: Option<&str>)
+    -> io::Result<(TableData, String)>
+{
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+
+    let mut tables = markup_tables(&text, format);
+
+    let index = match table {
+        Some(name) => tables.iter().position(|t| t.name == name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("there is no {} table named {}", format, name)))?,
+        None if tables.is_empty() =>
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("no {} tables were found", format))),
+        None => 0
+    };
+
+    let found = tables.swap_remove(index);
+
+    Ok((found.table, found.name))
+}
+
+/// Find the tables of a document in the order they start in it
+pub fn markup_tables(text: &str, format: MarkupFormat) -> Vec<MarkupTable> {
+    match format {
+        MarkupFormat::Html => html_tables(text),
+        MarkupFormat::Markdown => markdown_tables(text),
+    }
+}
+
+/// Find the `<table>` elements of an HTML page
+///
+/// The first row is the header if it is in a `<thead>` or all of its cells are `<th>`. A
+/// cell spanning several columns or rows fills the others it covers with empty fields and
+/// short rows are padded. Cells hold their text without markup, with entities decoded and
+/// whitespace collapsed. A table nested in a cell is a table of its own. Tables are named
+/// by their `<caption>`, else their id attribute, else "Table 1", "Table 2" and so on
+pub fn html_tables(text: &str) -> Vec<MarkupTable> {
+    let lines = LineStarts::new(text);
+    // tables are kept in the order they start, which for nested tables is not the order they end
+    let mut found: Vec<Option<Grid>> = Vec::new();
+    let mut open: Vec<(usize, HtmlTable)> = Vec::new();
+    let mut pos = 0;
+
+    while pos < text.len() {
+        let next = text[pos..].find('<').map_or(text.len(), |i| pos + i);
+
+        if let Some((_, table)) = open.last_mut() {
+            table.text(&text[pos..next]);
+        }
+
+        if next == text.len() {
+            break;
+        }
+
+        let rest = &text[next..];
+
+        if rest.starts_with("<!--") {
+            pos = rest.find("-->").map_or(text.len(), |i| next + i + 3);
+            continue;
+        }
+
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            pos = rest.find('>').map_or(text.len(), |i| next + i + 1);
+            continue;
+        }
+
+        let (tag, end) = match parse_tag(text, next) {
+            Some(tag) => tag,
+            None => {
+                // a "<" that does not start a tag is text
+                if let Some((_, table)) = open.last_mut() {
+                    table.text("<");
+                }
+
+                pos = next + 1;
+                continue;
+            }
+        };
+
+        pos = end;
+
+        match (tag.name.as_str(), tag.closing) {
+            ("table", false) => {
+                found.push(None);
+                open.push((found.len() - 1, HtmlTable::new(tag.attribute("id"))));
+            },
+            ("table", true) => if let Some((slot, table)) = open.pop() {
+                found[slot] = Some(table.finish());
+            },
+            // the contents of scripts and styles are not text
+            ("script", false) | ("style", false) => {
+                let close = format!("</{}", tag.name);
+                pos = text[pos..].to_ascii_lowercase().find(&close)
+                    .and_then(|i| text[pos + i..].find('>').map(|j| pos + i + j + 1))
+                    .unwrap_or(text.len());
+            },
+            _ => if let Some((_, table)) = open.last_mut() {
+                table.tag(&tag, lines.source(next));
+            }
+        }
+    }
+
+    // tables still open at the end of the page end with it
+    while let Some((slot, table)) = open.pop() {
+        found[slot] = Some(table.finish());
+    }
+
+    name_tables(found.into_iter().flatten().collect())
+}
+
+/// Find the pipe tables of a Markdown document
+///
+/// A table is a header row followed by a delimiter row such as `| --- | :-: |` with as
+/// many cells, and ends at a blank line or the start of another block. Rows are cut or
+/// padded to the width of the header and `\|` is a pipe inside a cell. Cells keep their
+/// inline Markdown. Tables in fenced code blocks are left out. Tables are named by the
+/// heading above them, else "Table 1", "Table 2" and so on
+pub fn markdown_tables(text: &str) -> Vec<MarkupTable> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    for line in text.split('\n') {
+        lines.push((offset, line.strip_suffix('\r').unwrap_or(line)));
+        offset += line.len() + 1;
+    }
+
+    let mut grids = Vec::new();
+    let mut heading = None;
+    let mut fence: Option<&str> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i].1;
+        let trimmed = line.trim_start();
+
+        if let Some(f) = fence {
+            if trimmed.starts_with(f) {
+                fence = None;
+            }
+
+            i += 1;
+            continue;
+        }
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            i += 1;
+            continue;
+        }
+
+        if let Some(text) = heading_text(line) {
+            heading = Some(text);
+            i += 1;
+            continue;
+        }
+
+        let header = match lines.get(i + 1).and_then(|(_, delimiter)| table_header(line, delimiter)) {
+            Some(header) => header,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+
+        let cols = header.len();
+        let mut grid = Grid { name: heading.clone(), header: Some(header), .. Grid::default() };
+
+        i += 2;
+
+        while let Some(&(offset, line)) = lines.get(i) {
+            if line.trim().is_empty() || starts_block(line) {
+                break;
+            }
+
+            let mut cells = split_row(line);
+            cells.resize(cols, String::new());
+
+            grid.rows.push(cells);
+            grid.sources.push(RecordSource { line: i + 1, offset });
+            i += 1;
+        }
+
+        grids.push(grid);
+    }
+
+    name_tables(grids)
+}
+
+fn markup_format(filename: &str) -> io::Result<MarkupFormat> {
+    MarkupFormat::from_filename(filename)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not an HTML or Markdown file", filename)))
+}
+
+/// Rows of a table as they were found, before they are made the same width
+#[derive(Default)]
+struct Grid {
+    name: Option<String>,
+    header: Option<Vec<String>>,
+    rows: Vec<Vec<String>>,
+    sources: Vec<RecordSource>,
+}
+
+impl Grid {
+    fn columns(&self) -> usize {
+        self.header.iter().chain(&self.rows).map(|r| r.len()).max().unwrap_or(0)
+    }
+
+    fn into_table(self) -> TableData {
+        let cols = self.columns();
+        let mut table = TableData::new();
+
+        if let Some(mut header) = self.header {
+            header.resize(cols, String::new());
+            table.set_header(&mut header);
+        }
+
+        for mut row in self.rows {
+            row.resize(cols, String::new());
+            table.set_data(&mut row, cols);
+        }
+
+        table.sources = self.sources;
+        table
+    }
+}
+
+/// Give every table a name unique within its document, leaving out tables without cells
+fn name_tables(grids: Vec<Grid>) -> Vec<MarkupTable> {
+    let mut tables: Vec<MarkupTable> = Vec::new();
+
+    for grid in grids.into_iter().filter(|g| g.columns() > 0) {
+        let base = grid.name.clone()
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| format!("Table {}", tables.len() + 1));
+
+        let mut name = base.clone();
+        let mut n = 1;
+
+        while tables.iter().any(|t| t.name == name) {
+            n += 1;
+            name = format!("{} ({})", base, n);
+        }
+
+        tables.push(MarkupTable { name, table: grid.into_table() });
+    }
+
+    tables
+}
+
+/// Byte offsets of the starts of the lines of a document
+struct LineStarts(Vec<usize>);
+
+impl LineStarts {
+    fn new(text: &str) -> Self {
+        LineStarts(std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect())
+    }
+
+    fn source(&self, offset: usize) -> RecordSource {
+        RecordSource { line: self.0.partition_point(|&s| s <= offset), offset }
+    }
+}
+
+/// A start or end tag of an HTML element
+struct Tag {
+    /// Lowercase name of the element
+    name: String,
+    closing: bool,
+    /// Lowercase names and decoded values
+    attributes: Vec<(String, String)>,
+}
+
+impl Tag {
+    fn attribute(&self, name: &str) -> Option<String> {
+        self.attributes.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone())
+    }
+
+    fn span(&self, name: &str, max: usize) -> usize {
+        self.attribute(name).and_then(|v| v.trim().parse().ok()).unwrap_or(1).clamp(1, max)
+    }
+}
+
+/// Parse the tag starting with the "<" at start. Returns the tag and the offset after it,
+/// or None if the "<" does not start a tag
+fn parse_tag(text: &str, start: usize) -> Option<(Tag, usize)> {
+    let bytes = text.as_bytes();
+    let len = bytes.len();
+    let mut i = start + 1;
+
+    let closing = bytes.get(i) == Some(&b'/');
+
+    if closing {
+        i += 1;
+    }
+
+    let name_start = i;
+
+    while i < len && bytes[i].is_ascii_alphanumeric() {
+        i += 1;
+    }
+
+    if i == name_start || !bytes[name_start].is_ascii_alphabetic() {
+        return None;
+    }
+
+    let mut tag = Tag { name: text[name_start..i].to_ascii_lowercase(), closing, attributes: Vec::new() };
+
+    loop {
+        while i < len && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            i += 1;
+        }
+
+        // a tag left open runs to the end of the page
+        if i == len {
+            return Some((tag, len));
+        }
+
+        if bytes[i] == b'>' {
+            return Some((tag, i + 1));
+        }
+
+        let name_start = i;
+
+        while i < len && !bytes[i].is_ascii_whitespace() && !matches!(bytes[i], b'=' | b'>' | b'/') {
+            i += 1;
+        }
+
+        if i == name_start {
+            // a stray "=" without a name
+            i += 1;
+            continue;
+        }
+
+        let name = text[name_start..i].to_ascii_lowercase();
+
+        while i < len && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+
+        let mut value = String::new();
+
+        if i < len && bytes[i] == b'=' {
+            i += 1;
+
+            while i < len && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+
+            if i < len && (bytes[i] == b'"' || bytes[i] == b'\'') {
+                let quote = bytes[i] as char;
+                let value_start = i + 1;
+                let value_end = text[value_start..].find(quote).map_or(len, |e| value_start + e);
+
+                value = decode_entities(&text[value_start..value_end]);
+                i = (value_end + 1).min(len);
+            } else {
+                let value_start = i;
+
+                while i < len && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+                    i += 1;
+                }
+
+                value = decode_entities(&text[value_start..i]);
+            }
+        }
+
+        tag.attributes.push((name, value));
+    }
+}
+
+/// Decode the character references of HTML text e.g. "&amp;" and "&#x41;"
+///
+/// Only the named references of XML and &nbsp; are known, others are left as they are
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(i) = rest.find('&') {
+        decoded.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let reference = rest[1..].find(';')
+            .filter(|&e| e <= 10)
+            .and_then(|e| entity(&rest[1..e + 1]).map(|c| (c, e + 2)));
+
+        match reference {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            },
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let code = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => name.strip_prefix('#').and_then(|d| d.parse().ok())
+            };
+
+            code.and_then(char::from_u32)
+        }
+    }
+}
+
+/// A row of an HTML table being read
+struct HtmlRow {
+    cells: Vec<String>,
+    /// Every cell so far is a header cell
+    header: bool,
+    source: RecordSource,
+}
+
+/// A cell of an HTML table being read
+struct HtmlCell {
+    text: String,
+    header: bool,
+    colspan: usize,
+    rowspan: usize,
+}
+
+/// An HTML table being read
+struct HtmlTable {
+    grid: Grid,
+    id: Option<String>,
+    caption: Option<String>,
+    in_caption: bool,
+    in_head: bool,
+    row: Option<HtmlRow>,
+    cell: Option<HtmlCell>,
+    /// Rows read so far including the header
+    rows: usize,
+    /// For every column, the row up to which it is covered by a cell spanning rows above
+    covered: Vec<usize>,
+}
+
+impl HtmlTable {
+    fn new(id: Option<String>) -> Self {
+        HtmlTable {
+            grid: Grid::default(),
+            id,
+            caption: None,
+            in_caption: false,
+            in_head: false,
+            row: None,
+            cell: None,
+            rows: 0,
+            covered: Vec::new(),
+        }
+    }
+
+    /// Text found between tags, which is kept if it is in a cell or the caption
+    fn text(&mut self, text: &str) {
+        if self.in_caption {
+            self.caption.get_or_insert_with(String::new).push_str(&decode_entities(text));
+        } else if let Some(cell) = &mut self.cell {
+            cell.text.push_str(&decode_entities(text));
+        }
+    }
+
+    fn tag(&mut self, tag: &Tag, source: RecordSource) {
+        match (tag.name.as_str(), tag.closing) {
+            ("caption", closing) => self.in_caption = !closing,
+            ("thead", closing) => {
+                self.end_row();
+                self.in_head = !closing;
+            },
+            ("tbody", _) | ("tfoot", _) => {
+                self.end_row();
+                self.in_head = false;
+            },
+            ("tr", false) => {
+                self.end_row();
+                self.row = Some(HtmlRow { cells: Vec::new(), header: true, source });
+            },
+            ("tr", true) => self.end_row(),
+            ("td", false) | ("th", false) => {
+                self.end_cell();
+
+                // cells outside a row start one
+                if self.row.is_none() {
+                    self.row = Some(HtmlRow { cells: Vec::new(), header: true, source });
+                }
+
+                self.cell = Some(HtmlCell {
+                    text: String::new(),
+                    header: tag.name == "th" || self.in_head,
+                    colspan: tag.span("colspan", MAX_COLSPAN),
+                    rowspan: tag.span("rowspan", MAX_ROWSPAN),
+                });
+            },
+            ("td", true) | ("th", true) => self.end_cell(),
+            // elements that break lines separate words
+            ("br", _) | ("p", _) | ("div", _) | ("li", _) => self.text(" "),
+            _ => {}
+        }
+    }
+
+    /// Skip the columns of the current row covered by cells spanning rows above it
+    fn skip_covered(&mut self) {
+        let rows = self.rows;
+
+        if let Some(row) = &mut self.row {
+            while self.covered.get(row.cells.len()).map_or(false, |&until| until > rows) {
+                row.cells.push(String::new());
+            }
+        }
+    }
+
+    fn end_cell(&mut self) {
+        let cell = match self.cell.take() {
+            Some(cell) => cell,
+            None => return
+        };
+
+        self.skip_covered();
+
+        let row = match &mut self.row {
+            Some(row) => row,
+            None => return
+        };
+
+        let col = row.cells.len();
+
+        row.header &= cell.header;
+        row.cells.push(cell.text.split_whitespace().collect::<Vec<_>>().join(" "));
+        row.cells.resize(col + cell.colspan, String::new());
+
+        if cell.rowspan > 1 {
+            if self.covered.len() < col + cell.colspan {
+                self.covered.resize(col + cell.colspan, 0);
+            }
+
+            for until in &mut self.covered[col..col + cell.colspan] {
+                *until = self.rows + cell.rowspan;
+            }
+        }
+    }
+
+    fn end_row(&mut self) {
+        self.end_cell();
+        self.skip_covered();
+
+        let row = match self.row.take() {
+            Some(row) if !row.cells.is_empty() => row,
+            _ => return
+        };
+
+        self.rows += 1;
+
+        if row.header && self.grid.header.is_none() && self.grid.rows.is_empty() {
+            self.grid.header = Some(row.cells);
+        } else {
+            self.grid.rows.push(row.cells);
+            self.grid.sources.push(row.source);
+        }
+    }
+
+    fn finish(mut self) -> Grid {
+        self.end_row();
+
+        let caption = self.caption.map(|c| c.split_whitespace().collect::<Vec<_>>().join(" "));
+        self.grid.name = caption.filter(|c| !c.is_empty()).or(self.id);
+        self.grid
+    }
+}
+
+/// Text of an ATX heading such as "## Prices ##"
+fn heading_text(line: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    let rest = &trimmed[level..];
+
+    if level == 0 || level > 6 || !(rest.is_empty() || rest.starts_with(' ') || rest.starts_with('\t')) {
+        return None;
+    }
+
+    Some(rest.trim().trim_end_matches('#').trim_end().to_string())
+}
+
+/// A line that starts another block and so ends a table
+fn starts_block(line: &str) -> bool {
+    let trimmed = line.trim_start();
+
+    heading_text(line).is_some() || trimmed.starts_with('>')
+        || trimmed.starts_with("```") || trimmed.starts_with("~~~")
+}
+
+/// The cells of the header of a table if the lines are a header and a delimiter row
+fn table_header(line: &str, delimiter: &str) -> Option<Vec<String>> {
+    let indented = |l: &str| l.len() - l.trim_start().len() > 3;
+
+    if !line.contains('|') || !delimiter.contains('|') || indented(line) || indented(delimiter) {
+        return None;
+    }
+
+    let header = split_row(line);
+    let delimiters = split_row(delimiter);
+
+    if header.len() != delimiters.len() || !delimiters.iter().all(|d| is_delimiter_cell(d)) {
+        return None;
+    }
+
+    Some(header)
+}
+
+/// A cell of a delimiter row such as "---", ":--" or ":-:"
+fn is_delimiter_cell(cell: &str) -> bool {
+    let cell = cell.strip_prefix(':').unwrap_or(cell);
+    let cell = cell.strip_suffix(':').unwrap_or(cell);
+
+    !cell.is_empty() && cell.chars().all(|c| c == '-')
+}
+
+/// Split a row of a pipe table into trimmed cells. Leading and trailing pipes are optional
+fn split_row(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = if line.ends_with('|') && !line.ends_with("\\|") { &line[..line.len() - 1] } else { line };
+
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cell.push('|');
+                chars.next();
+            },
+            '|' => {
+                cells.push(cell.trim().to_string());
+                cell.clear();
+            },
+            _ => cell.push(c)
+        }
+    }
+
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    macro_rules! make_strvec {
+        [ $($a:expr),+ ] => {
+            vec![ $($a.to_owned()),+ ]
+        }
+    }
+
+    #[test]
+    fn test_markup_format() {
+        assert_eq!(MarkupFormat::from_filename("Page.HTM"), Some(MarkupFormat::Html));
+        assert_eq!(MarkupFormat::from_filename("README.md.gz"), Some(MarkupFormat::Markdown));
+        assert_eq!(MarkupFormat::from_filename("data.csv"), None);
+    }
+
+    #[test]
+    fn test_html_tables() {
+        let page = "<html><head><style>td { color: red }</style></head><body>\n\
+                    <table id=prices>\n\
+                    <thead><tr><td>Name<td>Value</tr></thead>\n\
+                    <tr><td>value1 &amp;<br>more</td><td>10</td></tr>\n\
+                    <tr><td colspan=\"2\">total &lt;&#x41;&gt;</td></tr>\n\
+                    </table>\n\
+                    <!-- <table><tr><td>commented out</td></tr></table> -->\n\
+                    <TABLE><CAPTION> Stock\n levels </CAPTION>\n\
+                    <TR><TH>Name</TH><TD>Stock</TD></TR>\n\
+                    <TR><TD rowspan=2>value1</TD><TD>3 < 4</TD></TR>\n\
+                    <TR><TD>5</TD></TR>\n\
+                    </TABLE><table></table><table><tr><td>x</td></tr></table></body></html>";
+
+        let tables = html_tables(page);
+        let names = tables.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec![ "prices", "Stock levels", "Table 3" ]);
+
+        let prices = &tables[0].table;
+        assert_eq!(prices.header(), &make_strvec![ "Name", "Value" ]);
+        assert_eq!(prices.data(), &make_strvec![ "value1 & more", "10", "total <A>", "" ]);
+        assert_eq!(prices.source(0), Some(RecordSource { line: 4, offset: page.find("<tr><td>value1").unwrap() }));
+
+        // a row with a data cell is not the header
+        let stock = &tables[1].table;
+        assert_eq!(stock.has_headers(), false);
+        assert_eq!(stock.data(), &make_strvec![ "Name", "Stock", "value1", "3 < 4", "", "5" ]);
+
+        assert_eq!(tables[2].table.data(), &make_strvec![ "x" ]);
+    }
+
+    #[test]
+    fn test_html_nested_tables() {
+        let page = "<table><tr><th>Outer</th></tr><tr><td>before \
+                    <table><tr><td>inner</td></tr></table> after</td></tr></table>\
+                    <table><caption>Outer</caption><tr><td>again</td></tr></table>";
+
+        let tables = html_tables(page);
+        let names = tables.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec![ "Table 1", "Table 2", "Outer" ]);
+
+        assert_eq!(tables[0].table.header(), &make_strvec![ "Outer" ]);
+        assert_eq!(tables[0].table.data(), &make_strvec![ "before after" ]);
+        assert_eq!(tables[1].table.data(), &make_strvec![ "inner" ]);
+    }
+
+    #[test]
+    fn test_markdown_tables() {
+        let document = "# Prices\r\n\
+                        \r\n\
+                        | Name | Value |\r\n\
+                        |:-----|------:|\r\n\
+                        | value1 | 10 |\r\n\
+                        value2 | a \\| b | extra\r\n\
+                        | value3 |\r\n\
+                        \r\n\
+                        ```\r\n\
+                        | Code | Block |\r\n\
+                        | ---- | ----- |\r\n\
+                        ```\r\n\
+                        A | B\r\n\
+                        - | -\r\n\
+                        1 | 2\r\n\
+                        ## Prices\r\n\
+                        Name | Value\r\n\
+                        --- | --- | ---\r\n\
+                        x | y\r\n\
+                        --- | ---\r\n";
+
+        let tables = markdown_tables(document);
+        let names = tables.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec![ "Prices", "Prices (2)", "Prices (3)" ]);
+
+        let prices = &tables[0].table;
+        assert_eq!(prices.header(), &make_strvec![ "Name", "Value" ]);
+        assert_eq!(prices.data(), &make_strvec![ "value1", "10", "value2", "a | b", "value3", "" ]);
+        assert_eq!(prices.source(1), Some(RecordSource { line: 6, offset: document.find("value2").unwrap() }));
+
+        // a table ends at a heading
+        assert_eq!(tables[1].table.header(), &make_strvec![ "A", "B" ]);
+        assert_eq!(tables[1].table.data(), &make_strvec![ "1", "2" ]);
+
+        // a delimiter row needs as many cells as the header
+        assert_eq!(tables[2].table.header(), &make_strvec![ "x", "y" ]);
+        assert_eq!(tables[2].table.has_data(), false);
+    }
+
+    #[test]
+    fn test_from_markup_reader() {
+        let document = "| a |\n|---|\n| 1 |\n\n| b |\n|---|\n| 2 |\n";
+
+        let (table, name) = from_markup_reader(Cursor::new(document), MarkupFormat::Markdown, None)
+            .expect("read error");
+        assert_eq!((table.data(), name.as_str()), (&make_strvec![ "1" ], "Table 1"));
+
+        let (table, _) = from_markup_reader(Cursor::new(document), MarkupFormat::Markdown, Some("Table 2"))
+            .expect("read error");
+        assert_eq!(table.header(), &make_strvec![ "b" ]);
+
+        let e = from_markup_reader(Cursor::new(document), MarkupFormat::Markdown, Some("Table 3")).err();
+        assert_eq!(e.map(|e| e.kind()), Some(io::ErrorKind::NotFound));
+
+        let e = from_markup_reader(Cursor::new("<p>no tables</p>"), MarkupFormat::Html, None).err();
+        assert_eq!(e.map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+    }
+}
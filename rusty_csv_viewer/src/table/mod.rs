@@ -1,6 +1,40 @@
 // Bring these into the table namespace
+pub use archive::{is_zip_file, list_members, member_path, open_member, split_member_path, MEMBER_SEPARATOR};
+pub use columnar::{detect_columnar_file, ColumnarFormat, ColumnarTable};
+pub use compression::{detect_compression, Compression, DecompressReader};
 pub use data::*;
-pub use reader::{from_csv_file, from_tsv_file};
+pub use dialect::{Dialect, LineEnding, LineEndings, Separator, Terminator, Trim};
+pub use encoding::{detect_encoding, DecodeReader, DetectedEncoding, Encoding};
+pub use fixed::{from_fixed_file, from_fixed_reader, infer_fixed_file, FixedColumn, FixedLayout, FIXED_SAMPLE_LINES};
+pub use header::{detect_header, FieldType};
+pub use json::{from_json_file, from_json_reader, from_ndjson_file, from_ndjson_reader, is_json_file, Flatten, JsonLayout,
+               JsonOptions, JSON_EXTENSIONS, KEY_SEPARATOR};
+pub use loader::{is_cancelled, load, CancelToken, LoadRequest, LoadResult, Loaded, Loader, Progress};
+pub use mapped::MappedTable;
+pub use markup::{from_markup_file, from_markup_reader, html_tables, is_markup_file, list_markup_tables, markdown_tables,
+                 markup_tables, MarkupFormat, MarkupTable, HTML_EXTENSIONS, MARKDOWN_EXTENSIONS};
+pub use parallel::{from_file_parallel, from_slice_parallel, from_slice_parallel_with};
+pub use reader::{from_csv_file, from_file, from_file_encoded, from_read, from_reader, from_tsv_file, is_stdin, is_stream,
+                 open_file, Records, STDIN_FILENAME};
+pub use sniffer::{sniff, sniff_file, sniff_reader, Sniffed, SNIFF_SAMPLE_SIZE};
+pub use sqlite::{from_query, from_query_with, is_sqlite_file, list_tables, table_query, SQLITE_EXTENSIONS};
+pub use workbook::{cell_text, from_range, from_sheet, is_workbook_file, list_sheets, WORKBOOK_EXTENSIONS};
 
 mod reader;
-mod data;
\ No newline at end of file
+mod archive;
+mod columnar;
+mod compression;
+mod data;
+mod dialect;
+mod encoding;
+mod fixed;
+mod header;
+mod json;
+mod loader;
+mod mapped;
+mod markup;
+mod parallel;
+mod scan;
+mod sniffer;
+mod sqlite;
+mod workbook;
@@ -0,0 +1,266 @@
+use std::cmp;
+use std::io::{self, Read};
+use std::thread;
+
+use crate::table::data::{TableData, TableDataValidationError};
+use crate::table::dialect::Dialect;
+use crate::table::encoding::{DecodeReader, DetectedEncoding, Encoding};
+use crate::table::reader::{build_table_with, open_file, Records};
+use crate::table::scan::{can_scan, RecordStart, RecordStarts};
+
+type TableResult<T> = Result<T, TableDataValidationError>;
+
+/// Smallest part of the input that is parsed on its own thread
+const PARALLEL_MIN_CHUNK: usize = 1024 * 1024;
+
+/// Read a delimited file in the given encoding or a detected one if None, parsing
+/// parts of it on every available core
+///
+/// The whole file is decoded into memory first. Results are the same as from_file_encoded
+pub fn from_file_parallel(filename: &str, dialect: &Dialect, encoding: Option<Encoding>)
+    -> io::Result<(TableResult<TableData>, DetectedEncoding)>
+{
+    let mut decoder = DecodeReader::new(open_file(filename)?, encoding)?;
+    let mut data = Vec::new();
+    decoder.read_to_end(&mut data)?;
+    let detected = decoder.encoding();
+
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+
+    Ok((from_slice_parallel(&data, dialect, threads)?, detected))
+}
+
+/// Parse UTF-8 delimited data split into up to the given number of parts on as many threads
+///
+/// The input is split at record boundaries found by a quick scan of its quotes and
+/// terminators. The parts are merged in order so rows, errors and row numbers match
+/// a single threaded parse
+pub fn from_slice_parallel(data: &[u8], dialect: &Dialect, threads: usize) -> io::Result<TableResult<TableData>> {
+    from_slice_parallel_with(data, dialect, threads, &|_| Ok(()))
+}
+
+/// Parse in parallel like from_slice_parallel, calling back from the parser threads after
+/// every record with the number of bytes read for it. An error from the callback stops
+/// the parse and is returned
+pub fn from_slice_parallel_with(data: &[u8], dialect: &Dialect, threads: usize,
+                                on_record: &(dyn Fn(usize) -> io::Result<()> + Sync))
+    -> io::Result<TableResult<TableData>>
+{
+    let starts = split_records(data, dialect, threads, PARALLEL_MIN_CHUNK);
+    parse_parts(data, dialect, &starts, on_record)
+}
+
+/// Pick the record starts that split the input into about equal parts of at least
+/// min_chunk bytes. The first part always starts at the beginning of the input
+fn split_records(data: &[u8], dialect: &Dialect, parts: usize, min_chunk: usize) -> Vec<RecordStart> {
+    let mut starts = vec![ RecordStart { offset: 0, line: 1 } ];
+    let parts = cmp::min(parts, data.len() / cmp::max(min_chunk, 1));
+
+    if parts < 2 || !can_scan(dialect) {
+        return starts;
+    }
+
+    let size = data.len() / parts;
+    let mut records = RecordStarts::new(data, 0, dialect);
+
+    for part in 1..parts {
+        match records.find(|r| r.offset >= part * size) {
+            Some(start) => starts.push(start),
+            None => break
+        }
+    }
+
+    starts
+}
+
+/// Parse each part on its own thread and merge the tables in order
+fn parse_parts(data: &[u8], dialect: &Dialect, starts: &[RecordStart],
+               on_record: &(dyn Fn(usize) -> io::Result<()> + Sync))
+    -> io::Result<TableResult<TableData>>
+{
+    if starts.len() < 2 {
+        return build_table_with(Records::new(data, dialect), dialect, on_record);
+    }
+
+    // records in later parts are checked against the field count of the first record
+    let fields = match Records::new(&data[..starts[1].offset], dialect).next() {
+        Some(Ok(v)) => v.len(),
+        _ => 0
+    };
+
+    let results = thread::scope(|s| {
+        let handles = starts.iter().enumerate()
+            .map(|(i, start)| {
+                let end = starts.get(i+1).map_or(data.len(), |next| next.offset);
+                let part = &data[start.offset..end];
+
+                s.spawn(move || {
+                    if i == 0 {
+                        build_table_with(Records::new(part, dialect), dialect, on_record)
+                    } else {
+                        let dialect = Dialect { header: false, .. dialect.clone() };
+                        let records = Records::new(part, &dialect).continuing(start.line, start.offset, fields);
+                        build_table_with(records, &dialect, on_record)
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles.into_iter()
+            .map(|h| h.join().expect("a parser thread panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    merge_parts(results)
+}
+
+/// Join the tables of consecutive parts of the input
+///
+/// Row numbers of errors are made relative to the whole input. The first error of a
+/// strict parse is returned just as a single threaded parse would stop at it
+fn merge_parts(results: Vec<io::Result<TableResult<TableData>>>) -> io::Result<TableResult<TableData>> {
+    let mut table: Option<TableData> = None;
+    let mut rows = 0;
+
+    for result in results {
+        let mut part = match result? {
+            Ok(part) => part,
+            Err(mut e) => {
+                add_rows(&mut e, rows);
+                return Ok(Err(e));
+            }
+        };
+
+        for e in part.errors.iter_mut() {
+            add_rows(e, rows);
+        }
+
+        rows += part.rows();
+
+        let table = match table.as_mut() {
+            Some(t) => t,
+            None => {
+                table = Some(part);
+                continue;
+            }
+        };
+
+        table.errors.append(&mut part.errors);
+        table.line_endings.lf += part.line_endings.lf;
+        table.line_endings.crlf += part.line_endings.crlf;
+        table.line_endings.cr += part.line_endings.cr;
+
+        if part.has_data() {
+            // lenient parts can be wider than the table so far or the other way around
+            let cols = cmp::max(table.columns(), part.columns());
+            table.widen(cols);
+            part.widen(cols);
+
+            table.set_data(&mut part.data, cols);
+            table.sources.append(&mut part.sources);
+        }
+    }
+
+    Ok(Ok(table.unwrap_or_else(TableData::new)))
+}
+
+/// Move the row of an error found in a part of the input past the rows before it
+fn add_rows(error: &mut TableDataValidationError, rows: usize) {
+    match error {
+        TableDataValidationError::QuoteValidationError { row, .. } |
+        TableDataValidationError::RowFieldCountMismatchError { row, .. } |
+        TableDataValidationError::ReadError { row, .. } |
+        TableDataValidationError::JsonError { row, .. } => *row += rows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::reader::from_reader;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Parse with parts of a few bytes each and check against a single threaded parse
+    fn assert_same_as_sequential(data: &str, dialect: &Dialect, parts: usize) {
+        let starts = split_records(data.as_bytes(), dialect, parts, 1);
+        assert!(starts.len() > 1, "the input was not split");
+
+        let expected = from_reader(data.as_bytes(), dialect).expect("read error");
+        let r = parse_parts(data.as_bytes(), dialect, &starts, &|_| Ok(())).expect("read error");
+
+        match (r, expected) {
+            (Ok(r), Ok(expected)) => {
+                assert_eq!(r.header(), expected.header());
+                assert_eq!(r.data(), expected.data());
+                assert_eq!(r.dims, expected.dims);
+                assert_eq!(r.errors(), expected.errors());
+                assert_eq!(r.sources, expected.sources);
+                assert_eq!(r.line_endings, expected.line_endings);
+            },
+            (r, expected) => assert_eq!(r.err(), expected.err())
+        }
+    }
+
+    #[test]
+    fn test_split_records() {
+        let s = b"a,b\n\"c\nd\",e\nf,g\nh,i\n";
+        let starts = split_records(s, &Dialect::csv(), 3, 1);
+
+        assert_eq!(starts, vec![ RecordStart { offset: 0, line: 1 },
+                                 RecordStart { offset: 12, line: 4 },
+                                 RecordStart { offset: 16, line: 5 } ]);
+    }
+
+    #[test]
+    fn test_split_records_small_input() {
+        let s = b"a,b\nc,d\n";
+        assert_eq!(split_records(s, &Dialect::csv(), 8, 1024).len(), 1);
+    }
+
+    #[test]
+    fn test_parallel_valid() {
+        let s = "Name,Value\r\nvalue1,\"multi\r\nline\"\r\n\r\nvalue2,20\rvalue3,\"a, \"\"b\"\"\"\nvalue4,40";
+        let d = Dialect { header: true, .. Dialect::csv() };
+
+        assert_same_as_sequential(s, &d, 4);
+    }
+
+    #[test]
+    fn test_parallel_strict_error_row() {
+        let s = "a,b\nc,d\ne,f\ng,h\ni\nj,k\n";
+
+        assert_same_as_sequential(s, &Dialect::csv(), 4);
+        assert_same_as_sequential(s, &Dialect { header: true, .. Dialect::csv() }, 4);
+    }
+
+    #[test]
+    fn test_parallel_lenient_errors() {
+        let s = "a,b\nc,d\ne\nf,g,h\ni,5\" screen\nj,k\n\"l\n";
+        let d = Dialect { header: true, lenient: true, .. Dialect::csv() };
+
+        assert_same_as_sequential(s, &d, 5);
+    }
+
+    #[test]
+    fn test_parallel_callback() {
+        let s = "a,b\nc,d\ne,f\ng,h\n";
+        let d = Dialect::csv();
+        let starts = split_records(s.as_bytes(), &d, 2, 1);
+        let records = AtomicUsize::new(0);
+        let bytes = AtomicUsize::new(0);
+
+        let on_record = |n: usize| {
+            records.fetch_add(1, Ordering::Relaxed);
+            bytes.fetch_add(n, Ordering::Relaxed);
+            Ok(())
+        };
+        parse_parts(s.as_bytes(), &d, &starts, &on_record).expect("read error").expect("invalid data");
+
+        assert_eq!(records.into_inner(), 4);
+        assert_eq!(bytes.into_inner(), s.len());
+
+        // an error from the callback stops every thread
+        let r = parse_parts(s.as_bytes(), &d, &starts, &|_| Err(io::Error::new(io::ErrorKind::Other, "stop")));
+        assert_eq!(r.err().unwrap().to_string(), "stop");
+    }
+}
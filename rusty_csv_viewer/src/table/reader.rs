@@ -1,137 +1,937 @@
-use std::fs::File;
-use std::io::{self, Read};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
 use std::vec::Vec;
 
-use crate::table::data::{QuoteValidationError, TableData, TableDataValidationError};
+use regex::Regex;
+
+use crate::table::archive::{is_zip_file, open_member, split_member_path};
+use crate::table::compression::DecompressReader;
+use crate::table::data::{Location, QuoteValidationError, RecordSource, TableData, TableDataValidationError};
+use crate::table::dialect::{Dialect, LineEndings, Separator, Terminator};
+use crate::table::encoding::{DecodeReader, DetectedEncoding, Encoding};
 
 type TableResult<T> = Result<T, TableDataValidationError>;
 
-pub fn from_csv_file(filename: &str, header: bool) -> io::Result<TableResult<TableData>> {
-    let mut f = File::open(filename)?;
+/// Number of characters shown on each side of the failing character in an error snippet
+const SNIPPET_CONTEXT: usize = 30;
+
+/// Filename that reads stdin instead of a file e.g. `some_query | rusty_csv_viewer -`
+pub const STDIN_FILENAME: &str = "-";
 
-    let mut buffer = String::new();
-    f.read_to_string(&mut buffer)?;
+/// Test whether a filename stands for stdin, see STDIN_FILENAME
+pub fn is_stdin(filename: &str) -> bool {
+    filename == STDIN_FILENAME
+}
+
+/// Test whether a filename is stdin or a named pipe, which can only be read once and so
+/// can not be sniffed before it is read
+pub fn is_stream(filename: &str) -> bool {
+    is_stdin(filename) || fs::metadata(filename).map_or(false, |m| !m.is_file() && !m.is_dir())
+}
 
-    Ok(parse_values(&buffer, ',', header))
+pub fn from_csv_file(filename: &str, header: bool) -> io::Result<TableResult<TableData>> {
+    from_file(filename, &Dialect { header, .. Dialect::csv() })
 }
 
 pub fn from_tsv_file(filename: &str, header: bool) -> io::Result<TableResult<TableData>> {
-    let mut f = File::open(filename)?;
+    from_file(filename, &Dialect { header, .. Dialect::tsv() })
+}
+
+/// Read a delimited file described by a dialect
+/// The encoding is detected from the contents of the file
+pub fn from_file(filename: &str, dialect: &Dialect) -> io::Result<TableResult<TableData>> {
+    from_file_encoded(filename, dialect, None).map(|(r, _)| r)
+}
+
+/// Read a delimited file in the given encoding or a detected one if None
+///
+/// Returns the encoding the file was decoded with alongside the result
+pub fn from_file_encoded(filename: &str, dialect: &Dialect, encoding: Option<Encoding>)
+    -> io::Result<(TableResult<TableData>, DetectedEncoding)>
+{
+    let mut decoder = DecodeReader::new(open_file(filename)?, encoding)?;
+    let table = from_reader(BufReader::new(&mut decoder), dialect)?;
+
+    Ok((table, decoder.encoding()))
+}
+
+/// Read delimited data from any reader such as stdin or a named pipe in the given encoding
+/// or a detected one if None
+///
+/// The input is decompressed if it starts with the magic bytes of a known compression
+/// format and parsed as it is read. Returns the encoding the input was decoded with
+/// alongside the result
+pub fn from_read<R: Read>(reader: R, dialect: &Dialect, encoding: Option<Encoding>)
+    -> io::Result<(TableResult<TableData>, DetectedEncoding)>
+{
+    let mut decoder = DecodeReader::new(DecompressReader::new(reader)?, encoding)?;
+    let table = from_reader(BufReader::new(&mut decoder), dialect)?;
+
+    Ok((table, decoder.encoding()))
+}
+
+/// Open a file for reading, decompressing it if it starts with the magic bytes of a
+/// known compression format
+///
+/// A path such as "bundle.zip!/data.csv" reads a member of a ZIP archive. A ZIP archive
+/// itself can not be read as it may hold several tables, see archive::list_members.
+/// STDIN_FILENAME reads stdin
+pub fn open_file(filename: &str) -> io::Result<DecompressReader<'static>> {
+    match split_member_path(filename) {
+        (path, None) if is_stdin(path) => DecompressReader::new(io::stdin()),
+        (archive, Some(member)) => DecompressReader::new(open_member(archive, member)?),
+        (path, None) if is_zip_file(path) =>
+            Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is a ZIP archive, choose a member to open", path))),
+        (path, None) => DecompressReader::new(File::open(path)?)
+    }
+}
 
-    let mut buffer = String::new();
-    f.read_to_string(&mut buffer)?;
+/// Read delimited data from any buffered reader such as a file, stdin or an in-memory buffer
+///
+/// Io errors are returned in the outer result and validation errors in the inner result
+pub fn from_reader<R: BufRead>(reader: R, dialect: &Dialect) -> io::Result<TableResult<TableData>> {
+    build_table(Records::new(reader, dialect), dialect)
+}
 
-    Ok(parse_values(&buffer, '\t', header))
+/// Build a TableData from a stream of records
+///
+/// The first record becomes the header if requested. In lenient mode short records are
+/// padded with empty fields and long records widen the table with overflow columns.
+pub fn build_table<R: BufRead>(records: Records<R>, dialect: &Dialect) -> io::Result<TableResult<TableData>> {
+    build_table_with(records, dialect, |_| Ok(()))
 }
 
-fn parse_values(buffer: &str, delimiter: char, header: bool) -> TableResult<TableData> {
+/// Build a TableData from a stream of records, calling back after every record with the
+/// number of bytes read for it. An error from the callback stops the build and is returned
+pub fn build_table_with<R, F>(mut records: Records<R>, dialect: &Dialect, mut on_record: F)
+    -> io::Result<TableResult<TableData>>
+    where R: BufRead,
+          F: FnMut(usize) -> io::Result<()>
+{
     let mut csv_data = TableData::new();
-    let mut v: Vec<String> = Vec::new();
+    let mut offset = records.offset();
+
+    while let Some(record) = records.next() {
+        match record {
+            Ok(mut v) => {
+                on_record(records.offset() - offset)?;
+                offset = records.offset();
+
+                if dialect.lenient && (csv_data.has_headers() || csv_data.has_data()) {
+                    if v.len() > csv_data.columns() {
+                        csv_data.widen(v.len());
+                    }
+                    v.resize(csv_data.columns(), String::new());
+                }
 
-    let mut inside_quote = false;
-    let mut current_field = String::new();
-    let mut num_fields: usize = 0;
-    let mut prev_num_fields: usize = 0;
-    let mut row_count= 0;
-    let mut prev_char = '\0';
+                if dialect.header && !csv_data.has_headers() {
+                    csv_data.set_header(&mut v);
+                } else {
+                    let cols = v.len();
+                    csv_data.set_data(&mut v, cols);
+                    csv_data.sources.push(records.record_source());
+                }
+            },
+            // Hand read errors back to the caller as io errors
+            Err(TableDataValidationError::ReadError { kind, message, .. }) =>
+                return Err(io::Error::new(kind, message)),
+            Err(e) => return Ok(Err(e))
+        }
+    }
+
+    csv_data.line_endings = records.line_endings();
+    csv_data.errors = records.take_errors();
+    csv_data.preamble = records.take_preamble();
+
+    Ok(Ok(csv_data))
+}
+
+/// Streaming record parser over any buffered reader
+///
+/// Yields the fields of one record at a time so the input never has to be held
+/// in memory as a whole. Iteration stops after the first error.
+pub struct Records<R> {
+    reader: R,
+    dialect: Dialect,
+    /// The compiled expression of a regex separator
+    pattern: Option<Regex>,
+    /// The compiled expression of Dialect::skip_until
+    skip_until: Option<Regex>,
+    /// Why the separator or skip_until can not be used, returned instead of the first record
+    invalid: Option<io::Error>,
+    /// Lines are being skipped at the start of the input and the lines skipped so far
+    skipping: bool,
+    preamble: Vec<String>,
+    /// Decoded input that has not been parsed yet
+    chunk: String,
+    pos: usize,
+    bytes: Vec<u8>,
+    /// Parser state that is carried between chunks
+    inside_quote: bool,
+    escaped: bool,
+    /// Byte length of the current field up to its last escaped character, which a text
+    /// separator can not overlap
+    escaped_end: usize,
+    current_field: String,
+    fields: Vec<String>,
+    prev_char: char,
+    prev_num_fields: usize,
+    row_count: usize,
+    line_endings: LineEndings,
+    /// Source positions of the next character, the last character, the start of the
+    /// current field and the byte index of the last opening quote in the field
+    next_pos: Position,
+    char_pos: Position,
+    field_start: Position,
+    quote_open: usize,
+    /// Source position of the first character of the current record
+    record_start: Position,
+    record_started: bool,
+    last_cr: bool,
+    header_seen: bool,
+    /// Problems found in lenient mode
+    errors: Vec<TableDataValidationError>,
+    eof: bool,
+    done: bool,
+}
 
-    for c in buffer.chars().filter(|x| x != &'\r')
-                        .chain(std::iter::repeat('\n').take(1)) {
-        if c == prev_char && c == '\n' {
-            continue;
+impl<R: BufRead> Records<R> {
+    pub fn new(reader: R, dialect: &Dialect) -> Self {
+        let compiled = dialect.validate()
+            .and_then(|_| compile_separator(&dialect.separator))
+            .and_then(|pattern| compile_skip_until(&dialect.skip_until).map(|skip_until| (pattern, skip_until)));
+        let ((pattern, skip_until), invalid) = match compiled {
+            Ok(compiled) => (compiled, None),
+            Err(e) => ((None, None), Some(e))
+        };
+
+        Records {
+            reader,
+            dialect: dialect.clone(),
+            pattern,
+            skip_until,
+            invalid,
+            skipping: dialect.has_preamble(),
+            preamble: Vec::new(),
+            chunk: String::new(),
+            pos: 0,
+            bytes: Vec::new(),
+            inside_quote: false,
+            escaped: false,
+            escaped_end: 0,
+            current_field: String::new(),
+            fields: Vec::new(),
+            prev_char: '\0',
+            prev_num_fields: 0,
+            row_count: 0,
+            line_endings: LineEndings::default(),
+            next_pos: Position::start(),
+            char_pos: Position::start(),
+            field_start: Position::start(),
+            quote_open: 0,
+            record_start: Position::start(),
+            record_started: false,
+            last_cr: false,
+            header_seen: false,
+            errors: Vec::new(),
+            eof: false,
+            done: false,
         }
-        if (c != '\n' && c != delimiter) || (inside_quote && c == delimiter) {
-            current_field.push(c);
+    }
+
+    /// Continue parsing part way through a larger input such as one of the chunks
+    /// parsed in parallel
+    ///
+    /// The reader starts at a record boundary on the given line and byte offset of the
+    /// whole input and every record is expected to have the given number of fields
+    pub fn continuing(mut self, line: usize, offset: usize, fields: usize) -> Self {
+        self.next_pos = Position { line, offset, column: 1, cr: false };
+        self.prev_num_fields = fields;
+        self
+    }
+
+    /// Read the next line of input into the chunk buffer
+    ///
+    /// Lines are split after a '\n' or '\r' so a chunk always ends on a character boundary.
+    /// Returns false at the end of the input
+    fn read_chunk(&mut self) -> io::Result<bool> {
+        self.bytes.clear();
+
+        loop {
+            let (found, used) = {
+                let buf = self.reader.fill_buf()?;
+
+                if buf.is_empty() {
+                    break;
+                }
+
+                match buf.iter().position(|b| *b == b'\n' || *b == b'\r') {
+                    Some(mut i) => {
+                        // keep a '\r\n' in one line when both are in the buffer
+                        if buf[i] == b'\r' && buf.get(i+1) == Some(&b'\n') {
+                            i += 1;
+                        }
+
+                        self.bytes.extend_from_slice(&buf[..=i]);
+                        (true, i + 1)
+                    },
+                    None => {
+                        self.bytes.extend_from_slice(buf);
+                        (false, buf.len())
+                    }
+                }
+            };
+
+            self.reader.consume(used);
+
+            if found {
+                break;
+            }
         }
 
+        // keep the last line around for error snippets
+        if self.bytes.is_empty() {
+            return Ok(false);
+        }
+
+        self.chunk = String::from_utf8(std::mem::take(&mut self.bytes))
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData,
+                                        "stream did not contain valid UTF-8"))?;
+        self.pos = 0;
+
+        Ok(true)
+    }
+
+    fn next_char(&mut self) -> io::Result<Option<char>> {
+        loop {
+            if let Some(c) = self.chunk[self.pos..].chars().next() {
+                self.pos += c.len_utf8();
+                self.char_pos = self.next_pos;
+                self.next_pos.advance(c);
+                return Ok(Some(c));
+            }
+
+            if !self.read_chunk()? {
+                return Ok(None);
+            }
+
+            // skip the whole line if it is part of the preamble or a comment
+            if self.is_preamble_line() {
+                // a '\n' read apart from the '\r' before it ends the line already kept
+                if !(self.next_pos.cr && self.chunk == "\n") {
+                    self.preamble.push(self.chunk.trim_end_matches(|c| c == '\n' || c == '\r').to_owned());
+                }
+                self.skip_chunk();
+            } else if self.is_comment_line() {
+                self.skip_chunk();
+            }
+        }
+    }
+
+    /// Move past the rest of the current chunk without parsing it
+    fn skip_chunk(&mut self) {
+        for c in self.chunk[self.pos..].chars() {
+            self.next_pos.advance(c);
+        }
+        self.pos = self.chunk.len();
+    }
+
+    /// Test whether the current chunk is a line of the preamble, which ends at the line
+    /// after the first skip_lines lines that matches skip_until
+    fn is_preamble_line(&mut self) -> bool {
+        if !self.skipping {
+            return false;
+        }
+        if (self.next_pos.cr && self.chunk == "\n") || self.preamble.len() < self.dialect.skip_lines {
+            return true;
+        }
+
+        let line = self.chunk.trim_end_matches(|c| c == '\n' || c == '\r');
+        self.skipping = self.skip_until.as_ref().map_or(false, |p| !p.is_match(line));
+        self.skipping
+    }
+
+    /// Test whether the current chunk is a comment line outside of a record
+    fn is_comment_line(&self) -> bool {
+        match &self.dialect.comment {
+            Some(prefix) if !prefix.is_empty() =>
+                !self.inside_quote && self.fields.is_empty() && self.current_field.is_empty() &&
+                    self.chunk.starts_with(prefix.as_str()),
+            _ => false
+        }
+    }
+
+    /// Feed a single character to the parser
+    ///
+    /// Returns a record or an error once a row has been terminated
+    fn process_char(&mut self, c: char) -> Option<TableResult<Vec<String>>> {
+        let is_terminator = self.dialect.is_terminator(c);
+
+        // runs of whitespace are a single separator and whitespace at the start of a line
+        // is ignored, so only the first whitespace character after a field is processed
+        if self.dialect.separator == Separator::Whitespace && (c == ' ' || c == '\t') &&
+            !self.escaped && !self.inside_quote && self.current_field.is_empty() {
+            return None;
+        }
+
+        // a terminator can not start a record as blank lines are skipped
+        if !self.record_started && !is_terminator {
+            self.record_started = true;
+            self.record_start = self.char_pos;
+        }
+
+        if self.pattern.is_some() {
+            return self.process_regex_char(c, is_terminator);
+        }
+
+        // an escaped character is always part of the field
+        if self.escaped {
+            self.push_char(c);
+            self.escaped = false;
+            self.escaped_end = self.current_field.len();
+            self.prev_char = c;
+            return None;
+        }
+        if self.dialect.escape == Some(c) && c != self.dialect.quote {
+            self.push_char(c);
+            self.escaped = true;
+            self.prev_char = c;
+            return None;
+        }
+
+        let mut is_delimiter = match self.dialect.separator {
+            Separator::Delimiter => c == self.dialect.delimiter,
+            Separator::Whitespace => c == ' ' || c == '\t',
+            Separator::Text(_) | Separator::Regex(_) => false,
+        };
+
+        // quoted content is kept as is, including line breaks and blank lines
+        if self.inside_quote {
+            if c == '\n' && self.dialect.normalize_newlines && self.current_field.ends_with('\r') {
+                self.current_field.pop();
+            }
+        } else {
+            // the terminator added at the end of the input is not part of it
+            if self.dialect.terminator == Terminator::Default && !self.eof {
+                self.count_line_ending(c);
+            }
+
+            // skip blank lines and the '\n' of a '\r\n'
+            if is_terminator && self.dialect.is_terminator(self.prev_char) {
+                return None;
+            }
+        }
+        if self.inside_quote || (!is_terminator && !is_delimiter) {
+            self.push_char(c);
+        }
+
+        let prev_char = std::mem::replace(&mut self.prev_char, c);
+
         // change state if the character is a quote
-        inside_quote = if c == '"' { !inside_quote } else { inside_quote };
+        if c == self.dialect.quote && self.toggles_quote(prev_char) {
+            self.inside_quote = !self.inside_quote;
+
+            if self.inside_quote {
+                self.quote_open = self.current_field.len() - c.len_utf8();
+            }
+        }
         // only process a field or row when not inside a set of outer quotes
-        if !inside_quote {
-            // process the field. field either terminates in a comma or newline
-            if (c == '\n' && current_field.len() > 0) || c == delimiter {
-                if let Err(e) = validate_field(&current_field) {
-                    return Err(TableDataValidationError::QuoteValidationError {
-                        subtype: e, row: row_count+1, col: (v.len()+1) as i32, value: current_field
-                    });
-                }
+        if self.inside_quote {
+            return None;
+        }
 
-                v.push(finalize_field(&current_field));
-                current_field.clear();
-                num_fields += 1;
+        // a text separator is only known once its last character has been added to the field
+        if let Separator::Text(text) = &self.dialect.separator {
+            let len = self.current_field.len();
+
+            if len >= self.escaped_end + text.len() && self.current_field.ends_with(text.as_str()) {
+                self.current_field.truncate(len - text.len());
+                is_delimiter = true;
+            }
+        }
+
+        // process the field. field either terminates in a delimiter or terminator
+        if (is_terminator && self.current_field.len() > 0) || is_delimiter {
+            if let Err(e) = self.end_field() {
+                return Some(Err(e));
             }
+        }
+
+        // process the row. row ends in a terminator
+        if is_terminator && self.fields.len() > 0 {
+            return Some(self.end_record());
+        }
+
+        None
+    }
+
+    /// Feed a character to the parser when fields are separated by a regex
+    ///
+    /// A whole line is read into the current field before it is split at the matches of
+    /// the expression. Quotes and escapes are not special
+    fn process_regex_char(&mut self, c: char, is_terminator: bool) -> Option<TableResult<Vec<String>>> {
+        // the terminator added at the end of the input is not part of it
+        if self.dialect.terminator == Terminator::Default && !self.eof {
+            self.count_line_ending(c);
+        }
+
+        if !is_terminator {
+            self.push_char(c);
+            return None;
+        }
+        // skip blank lines and the '\n' of a '\r\n'
+        if self.current_field.is_empty() {
+            return None;
+        }
+
+        let line = std::mem::take(&mut self.current_field);
+        let line_start = self.field_start;
+        let mut bounds = Vec::new();
+        let mut start = 0;
+
+        // an expression that matches empty text such as `,*` does not split between characters
+        for m in self.pattern.as_ref()?.find_iter(&line).filter(|m| !m.as_str().is_empty()) {
+            bounds.push((start, m.start()));
+            start = m.end();
+        }
+        bounds.push((start, line.len()));
+
+        for (start, end) in bounds {
+            self.field_start = line_start;
+            for c in line[..start].chars() {
+                self.field_start.advance(c);
+            }
+
+            self.current_field.push_str(&line[start..end]);
+
+            if let Err(e) = self.end_field() {
+                return Some(Err(e));
+            }
+        }
 
-            // process the row. row ends in a newline
-            if c == '\n' && v.len() > 0 {
-                if prev_num_fields > 0 && num_fields != prev_num_fields {
-                    return Err(TableDataValidationError::RowFieldCountMismatchError {
-                        row: row_count+1, expected: prev_num_fields, found: num_fields
-                    });
+        Some(self.end_record())
+    }
+
+    /// Trim, check and unquote the current field and add it to the record
+    ///
+    /// Fails if the quotes of the field are invalid and the dialect is not lenient
+    fn end_field(&mut self) -> TableResult<()> {
+        if self.dialect.trims(self.is_header_row()) {
+            // leading whitespace never holds a line break so only the column moves
+            let trimmed = self.current_field.trim_start();
+            let skipped = &self.current_field[..self.current_field.len()-trimmed.len()];
+            self.field_start.offset += skipped.len();
+            self.field_start.column += skipped.chars().count();
+
+            self.current_field = self.current_field.trim().to_owned();
+        }
+
+        // fields separated by a regex are never quoted
+        if self.pattern.is_some() {
+            self.fields.push(std::mem::take(&mut self.current_field));
+            return Ok(());
+        }
+
+        match validate_field(&self.current_field, &self.dialect) {
+            Ok(_) => self.fields.push(finalize_field(&self.current_field, &self.dialect)),
+            Err((e, index)) => {
+                let error = TableDataValidationError::QuoteValidationError {
+                    subtype: e, row: self.row_count+1, col: self.fields.len()+1,
+                    value: self.current_field.clone(), location: self.locate_in_field(index)
+                };
+
+                if !self.dialect.lenient {
+                    self.current_field.clear();
+                    return Err(error);
                 }
 
-                prev_num_fields = num_fields;
-                num_fields = 0;
+                // keep the field as it was written
+                self.errors.push(error);
+                self.fields.push(self.current_field.clone());
+            }
+        }
 
-                if header && !csv_data.has_headers() {
-                    csv_data.set_header(&mut v);
-                } else {
-                    csv_data.set_data(&mut v, prev_num_fields);
-                    row_count += 1;
+        self.current_field.clear();
+        self.escaped_end = 0;
+
+        Ok(())
+    }
+
+    /// Check the number of fields of a terminated record and return it
+    ///
+    /// Fails if the record has a different number of fields to the one before it and the
+    /// dialect is not lenient
+    fn end_record(&mut self) -> TableResult<Vec<String>> {
+        let num_fields = self.fields.len();
+
+        if self.prev_num_fields > 0 && num_fields != self.prev_num_fields {
+            let error = TableDataValidationError::RowFieldCountMismatchError {
+                row: self.row_count+1, expected: self.prev_num_fields, found: num_fields,
+                location: self.locate_in_chunk(self.char_pos)
+            };
+
+            if !self.dialect.lenient {
+                return Err(error);
+            }
+
+            self.errors.push(error);
+        } else {
+            self.prev_num_fields = num_fields;
+        }
+
+        if self.is_header_row() {
+            self.header_seen = true;
+        } else {
+            self.row_count += 1;
+        }
+
+        self.record_started = false;
+        Ok(std::mem::take(&mut self.fields))
+    }
+
+    /// Add a character to the current field and remember where the field started
+    fn push_char(&mut self, c: char) {
+        if self.current_field.is_empty() {
+            self.field_start = self.char_pos;
+        }
+
+        self.current_field.push(c);
+    }
+
+    /// Locate a position on the line held in the chunk buffer
+    fn locate_in_chunk(&self, pos: Position) -> Location {
+        pos.locate(snippet(&self.chunk, pos.column - 1))
+    }
+
+    /// Locate the character at a character index of the current field
+    ///
+    /// The snippet comes from the chunk buffer when the character is on the line being
+    /// parsed, or else from the field text of an earlier line of a multi-line field
+    fn locate_in_field(&self, index: usize) -> Location {
+        let mut pos = self.field_start;
+        let mut line_start = 0;
+
+        for (i, (b, c)) in self.current_field.char_indices().enumerate() {
+            if i == index {
+                break;
+            }
+
+            pos.advance(c);
+
+            if c == '\n' || c == '\r' {
+                line_start = b + c.len_utf8();
+            }
+        }
+
+        if pos.line == self.char_pos.line {
+            return self.locate_in_chunk(pos);
+        }
+
+        let line = &self.current_field[line_start..];
+        // the first line of the field starts part way along the source line
+        let caret = if pos.line == self.field_start.line {
+            pos.column - self.field_start.column
+        } else {
+            pos.column - 1
+        };
+
+        pos.locate(snippet(line, caret))
+    }
+
+    /// Count the style of a line ending outside of quotes
+    fn count_line_ending(&mut self, c: char) {
+        if c == '\n' {
+            if self.last_cr {
+                self.line_endings.crlf += 1;
+            } else {
+                self.line_endings.lf += 1;
+            }
+        } else if self.last_cr {
+            self.line_endings.cr += 1;
+        }
+
+        self.last_cr = c == '\r';
+    }
+
+    /// The line endings found so far outside of quoted fields
+    pub fn line_endings(&self) -> LineEndings {
+        let mut line_endings = self.line_endings;
+
+        // a '\r' at the very end is not followed by anything
+        if self.last_cr && self.eof {
+            line_endings.cr += 1;
+        }
+
+        line_endings
+    }
+
+    /// Byte offset of the input read so far
+    pub fn offset(&self) -> usize {
+        self.next_pos.offset
+    }
+
+    /// Where the record returned last starts in the source
+    pub fn record_source(&self) -> RecordSource {
+        RecordSource { line: self.record_start.line, offset: self.record_start.offset }
+    }
+
+    /// Test whether a quote character opens or closes a quoted section
+    ///
+    /// In lenient mode a quote only opens a section at the start of a field or right after
+    /// a closing quote so stray quotes in unquoted fields are kept as data
+    fn toggles_quote(&self, prev_char: char) -> bool {
+        if !self.dialect.lenient || self.inside_quote {
+            return true;
+        }
+
+        let quote = self.dialect.quote;
+        // the quote has already been added to the field
+        let before = &self.current_field[..self.current_field.len()-quote.len_utf8()];
+
+        before.trim().is_empty() || (prev_char == quote && before.trim_start().starts_with(quote))
+    }
+
+    /// The errors skipped over in lenient mode so far
+    pub fn errors(&self) -> &[TableDataValidationError] {
+        &self.errors
+    }
+
+    /// Take the errors skipped over in lenient mode so far
+    pub fn take_errors(&mut self) -> Vec<TableDataValidationError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// The lines skipped at the start of the input so far, see Dialect::skip_lines
+    pub fn preamble(&self) -> &[String] {
+        &self.preamble
+    }
+
+    /// Take the lines skipped at the start of the input so far
+    pub fn take_preamble(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.preamble)
+    }
+
+    /// Test whether the record being parsed is the header
+    fn is_header_row(&self) -> bool {
+        self.dialect.header && !self.header_seen
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = TableResult<Vec<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        if let Some(e) = self.invalid.take() {
+            self.done = true;
+            return Some(Err(TableDataValidationError::ReadError {
+                row: 1, kind: e.kind(), message: e.to_string(), location: Position::start().locate(String::new())
+            }));
+        }
+
+        loop {
+            let c = match self.next_char() {
+                Ok(Some(c)) => c,
+                // terminate the last row as if the input ended in a terminator
+                Ok(None) if !self.eof && !self.inside_quote => {
+                    self.eof = true;
+                    self.char_pos = self.next_pos;
+                    self.dialect.terminator_char()
+                },
+                Ok(None) => break,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(TableDataValidationError::ReadError {
+                        row: self.row_count+1, kind: e.kind(), message: e.to_string(),
+                        location: self.next_pos.locate(String::new())
+                    }));
                 }
+            };
+
+            if let Some(r) = self.process_char(c) {
+                self.done = r.is_err();
+                return Some(r);
+            }
+        }
+
+        self.done = true;
+
+        // the parser might have not matched a set of quotes
+        if self.inside_quote {
+            // point at the quote that was never closed
+            let index = self.current_field[..self.quote_open].chars().count();
+            let location = self.locate_in_field(index);
+
+            let error = TableDataValidationError::QuoteValidationError {
+                subtype: QuoteValidationError::UnterminatedQuoteError,
+                row: self.row_count+1, col: self.fields.len()+1,
+                value: std::mem::take(&mut self.current_field), location
+            };
+
+            if !self.dialect.lenient {
+                return Some(Err(error));
+            }
+
+            // the rest of the input becomes the last field
+            if let TableDataValidationError::QuoteValidationError { value, .. } = &error {
+                self.fields.push(value.clone());
             }
+            self.errors.push(error);
+            self.inside_quote = false;
+            self.record_started = false;
+
+            return Some(Ok(std::mem::take(&mut self.fields)));
         }
 
-        prev_char = c;
+        None
     }
+}
 
-    // the parser might have not matched a set of quotes
-    if inside_quote {
-        return Err(TableDataValidationError::QuoteValidationError {
-            subtype: QuoteValidationError::UnterminatedQuoteError,
-            row: row_count+1, col: (v.len()+1) as i32, value: current_field
-        });
+/// A position in the decoded source
+#[derive(Debug,Clone,Copy)]
+struct Position {
+    line: usize,
+    offset: usize,
+    column: usize,
+    /// The previous character was a '\r' so a '\n' does not start another line
+    cr: bool,
+}
+
+impl Position {
+    fn start() -> Self {
+        Position { line: 1, offset: 0, column: 1, cr: false }
     }
 
-    Ok(csv_data)
+    /// Move past a character
+    fn advance(&mut self, c: char) {
+        self.offset += c.len_utf8();
+
+        if c == '\r' || (c == '\n' && !self.cr) {
+            self.line += 1;
+            self.column = 1;
+        } else if c != '\n' {
+            self.column += 1;
+        }
+
+        self.cr = c == '\r';
+    }
+
+    fn locate(&self, snippet: String) -> Location {
+        Location { line: self.line, offset: self.offset, column: self.column, snippet }
+    }
+}
+
+/// Cut the text around a character index of a line and mark it with a caret below
+///
+/// Tabs are repeated in the marker line so the caret lines up with the text
+pub fn snippet(line: &str, caret: usize) -> String {
+    let line = line.split(|c| c == '\n' || c == '\r').next().unwrap_or("");
+    let start = caret.saturating_sub(SNIPPET_CONTEXT);
+    let text = line.chars().skip(start).take(SNIPPET_CONTEXT*2 + 1).collect::<String>();
+    let marker = text.chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .chain(std::iter::repeat(' '))
+        .take(caret - start)
+        .collect::<String>();
+
+    format!("{}\n{}^", text, marker)
+}
+
+/// Compile the expression of a regex separator
+///
+/// Fails with InvalidInput if a text or regex separator is empty or the expression is invalid
+fn compile_separator(separator: &Separator) -> io::Result<Option<Regex>> {
+    match separator {
+        Separator::Text(text) | Separator::Regex(text) if text.is_empty() =>
+            Err(io::Error::new(io::ErrorKind::InvalidInput, "the separator is empty")),
+        Separator::Regex(expression) => Regex::new(expression)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)),
+        _ => Ok(None)
+    }
+}
+
+/// Compile the expression of Dialect::skip_until
+///
+/// Fails with InvalidInput if the expression is invalid
+fn compile_skip_until(skip_until: &Option<String>) -> io::Result<Option<Regex>> {
+    skip_until.as_ref()
+        .map(|expression| Regex::new(expression).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)))
+        .transpose()
 }
 
-fn validate_field(field: &str) -> Result<bool, QuoteValidationError> {
-    let has_outer_quotes = has_outer_quotes(&field);
-    // extract the quote indices skipping the outer quotes
-    let indices= field.chars().enumerate()
-                                .filter(|(i,v)|
-                                    { *v == '"' && (*i > 0 && *i < field.len()-1) })
-                                .map(|(i,_)| i).collect::<Vec<_>>();
+/// Check the quotes of a field
+///
+/// Returns the error and the character index of the offending quote
+fn validate_field(field: &str, dialect: &Dialect) -> Result<bool, (QuoteValidationError, usize)> {
+    let quote = dialect.quote;
+    let escape = dialect.escape.filter(|e| *e != quote);
+    let has_outer_quotes = has_outer_quotes(&field, quote);
+    let chars = field.chars().collect::<Vec<_>>();
+    // extract the unescaped quote indices skipping the outer quotes
+    let mut indices = Vec::new();
+    let mut escaped = false;
+
+    for (i, v) in chars.iter().enumerate() {
+        if escaped {
+            escaped = false;
+        } else if Some(*v) == escape {
+            escaped = true;
+        } else if *v == quote && (i > 0 && i < chars.len()-1) {
+            indices.push(i);
+        }
+    }
+
     // number of quotes must be even
     if indices.len() % 2 > 0 {
-        return Err(QuoteValidationError::InvalidQuoteError);
+        return Err((QuoteValidationError::InvalidQuoteError, indices[indices.len()-1]));
     }
 
     for v in indices.chunks(2) {
         if v[1] - v[0] > 1 {
-            return Err(QuoteValidationError::InvalidQuoteError);
+            return Err((QuoteValidationError::InvalidQuoteError, v[0]));
         }
         else if !has_outer_quotes {
-            return Err(QuoteValidationError::InvalidEscapeError);
+            return Err((QuoteValidationError::InvalidEscapeError, v[0]));
         }
     }
 
     Ok(true)
 }
 
-fn finalize_field(field: &str) -> String {
-    let mut finalized = String::from(field);
+fn finalize_field(field: &str, dialect: &Dialect) -> String {
+    let quote = dialect.quote;
+    let escape = dialect.escape.filter(|e| *e != quote);
+    let mut inner = field;
 
     // remove leading and trailing quotes
-    if has_outer_quotes(&finalized) {
-        finalized = finalized[1..finalized.len()-1].to_owned();
+    if has_outer_quotes(&field, quote) {
+        inner = &field[quote.len_utf8()..field.len()-quote.len_utf8()];
     }
 
-    finalized.replace("\"\"", "\"")
+    // resolve escaped characters and doubled quotes
+    let mut finalized = String::with_capacity(inner.len());
+    let mut chars = inner.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if Some(c) == escape {
+            finalized.push(chars.next().unwrap_or(c));
+        } else if c == quote && chars.peek() == Some(&quote) {
+            chars.next();
+            finalized.push(c);
+        } else {
+            finalized.push(c);
+        }
+    }
+
+    finalized
 }
 
-fn has_outer_quotes(field: &str) -> bool {
-    field.starts_with("\"") && field.ends_with("\"")
+fn has_outer_quotes(field: &str, quote: char) -> bool {
+    field.len() > quote.len_utf8() && field.starts_with(quote) && field.ends_with(quote)
 }
 
 #[cfg(test)]
@@ -142,6 +942,7 @@ mod tests {
     use std::path::Path;
 
     use super::*;
+    use crate::table::{LineEnding, Trim};
 
     macro_rules! make_strvec {
     [ $($a:expr),+ ]
@@ -150,125 +951,136 @@ mod tests {
         vec![ $($a.to_owned()),+ ]
     }
 }
+
+    fn location(line: usize, offset: usize, column: usize, snippet: &str) -> Location {
+        Location { line, offset, column, snippet: snippet.to_owned() }
+    }
+
+    fn parse_values(buffer: &str, delimiter: char, header: bool) -> TableResult<TableData> {
+        let dialect = Dialect { delimiter, header, .. Dialect::csv() };
+        // Reading from a str can not fail so only the validation result is of interest
+        from_reader(buffer.as_bytes(), &dialect).expect("reading from an in-memory buffer failed")
+    }
+
     // tests
     #[test]
     fn test_validate_field_none() {
         let s = "abc";
-        assert!(validate_field(&s).is_ok())
+        assert!(validate_field(&s, &Dialect::csv()).is_ok())
     }
 
     #[test]
     fn test_validate_field_outer_quotes_with_contents() {
         let s = "\"abc\"";
-        assert!(validate_field(&s).is_ok())
+        assert!(validate_field(&s, &Dialect::csv()).is_ok())
     }
 
     #[test]
     fn test_validate_field_outer_quotes_empty() {
         let s = "\"\"";
-        assert!(validate_field(&s).is_ok())
+        assert!(validate_field(&s, &Dialect::csv()).is_ok())
     }
 
     #[test]
     fn test_validate_field_invalid_escaped_quotes() {
         let s = "abc\"\"de";
-        let e = QuoteValidationError::InvalidEscapeError;
-        assert_eq!(validate_field(&s).err().unwrap(), e);
+        let e = (QuoteValidationError::InvalidEscapeError, 3);
+        assert_eq!(validate_field(&s, &Dialect::csv()).err().unwrap(), e);
     }
 
     #[test]
     fn test_validate_field_invalid_escaped_quotes2() {
         let s = "\"abc\"\"de";
-        let e = QuoteValidationError::InvalidEscapeError;
-        assert_eq!(validate_field(&s).err().unwrap(), e);
+        let e = (QuoteValidationError::InvalidEscapeError, 4);
+        assert_eq!(validate_field(&s, &Dialect::csv()).err().unwrap(), e);
     }
 
     #[test]
     fn test_validate_field_invalid_quotes_with_outer_single_quote() {
         let s = "\"\"\"";
-        let e = QuoteValidationError::InvalidQuoteError;
-        assert_eq!(validate_field(&s).err().unwrap(), e);
+        let e = (QuoteValidationError::InvalidQuoteError, 1);
+        assert_eq!(validate_field(&s, &Dialect::csv()).err().unwrap(), e);
     }
 
     #[test]
     fn test_validate_field_invalid_quotes_with_outer_with_many_single_quote() {
         let s = "\"abc\"de\"f\"";
-        let e = QuoteValidationError::InvalidQuoteError;
-        assert_eq!(validate_field(&s).err().unwrap(), e);
+        let e = (QuoteValidationError::InvalidQuoteError, 4);
+        assert_eq!(validate_field(&s, &Dialect::csv()).err().unwrap(), e);
     }
 
     #[test]
     fn test_validate_field_invalid_quotes_with_outer_with_inner_single_quote() {
         let s = "\"a\"bc\"";
-        let e = QuoteValidationError::InvalidQuoteError;
-        assert_eq!(validate_field(&s).err().unwrap(), e);
+        let e = (QuoteValidationError::InvalidQuoteError, 2);
+        assert_eq!(validate_field(&s, &Dialect::csv()).err().unwrap(), e);
     }
 
     #[test]
     fn test_validate_field_invalid_quotes_no_outer() {
         let s = "abc\"def";
-        let e = QuoteValidationError::InvalidQuoteError;
-        assert_eq!(validate_field(&s).err().unwrap(), e);
+        let e = (QuoteValidationError::InvalidQuoteError, 3);
+        assert_eq!(validate_field(&s, &Dialect::csv()).err().unwrap(), e);
     }
 
     #[test]
     fn test_validate_field_outer_quotes_with_one_valid_escape() {
         let s = "\"a\"\"bc\"";
-        assert!(validate_field(&s).is_ok())
+        assert!(validate_field(&s, &Dialect::csv()).is_ok())
     }
 
     #[test]
     fn test_validate_field_outer_quotes_with_many_valid_escapes() {
         let s = "\"a\"\"bcd\"\"efg\"\"\"";
-        assert!(validate_field(&s).is_ok())
+        assert!(validate_field(&s, &Dialect::csv()).is_ok())
     }
 
     #[test]
     fn test_has_outer_quotes_quoted() {
         let s = "\"abc\"";
-        assert_eq!(has_outer_quotes(&s), true)
+        assert_eq!(has_outer_quotes(&s, '"'), true)
     }
 
     #[test]
     fn test_has_outer_quotes_only_quotes() {
         let s = "\"\"";
-        assert_eq!(has_outer_quotes(&s), true)
+        assert_eq!(has_outer_quotes(&s, '"'), true)
     }
 
     #[test]
     fn test_has_outer_quotes_none() {
         let s = "a\"\"bc";
-        assert_eq!(has_outer_quotes(&s), false)
+        assert_eq!(has_outer_quotes(&s, '"'), false)
     }
 
     #[test]
     fn test_finalize_field_outer_quotes() {
         let s = "\"this is a value\"";
-        assert_eq!(finalize_field(&s), "this is a value")
+        assert_eq!(finalize_field(&s, &Dialect::csv()), "this is a value")
     }
 
     #[test]
     fn test_finalize_field_escaped_quotes() {
         let s = "\"this is a \"\"value\"\" that is quoted\"";
-        assert_eq!(finalize_field(&s), "this is a \"value\" that is quoted")
+        assert_eq!(finalize_field(&s, &Dialect::csv()), "this is a \"value\" that is quoted")
     }
 
     #[test]
     fn test_finalize_field_escaped_quotes2() {
         let s = "\"this is a \"\"\"\"value\"\" that\"\" is quoted\"";
-        assert_eq!(finalize_field(&s), "this is a \"\"value\" that\" is quoted")
+        assert_eq!(finalize_field(&s, &Dialect::csv()), "this is a \"\"value\" that\" is quoted")
     }
 
     #[test]
     fn test_finalize_field_no_quotes() {
         let s = "this is a string without quotes";
-        assert_eq!(finalize_field(&s), "this is a string without quotes")
+        assert_eq!(finalize_field(&s, &Dialect::csv()), "this is a string without quotes")
     }
 
     #[test]
     fn test_finalize_field_only_quotes() {
         let s = "\"\"";
-        assert_eq!(finalize_field(&s), "")
+        assert_eq!(finalize_field(&s, &Dialect::csv()), "")
     }
 
     #[test]
@@ -280,6 +1092,7 @@ mod tests {
             header: make_strvec![ "Name", "Type", "Value" ],
             data: vec![],
             dims: (3,0),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -297,6 +1110,7 @@ mod tests {
             header: make_strvec![ "Name", "Type", "Value" ],
             data: vec![],
             dims: (3,0),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -314,6 +1128,7 @@ mod tests {
             header: make_strvec![ "Name", "Type", "Value" ],
             data: vec![],
             dims: (3,0),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -331,6 +1146,7 @@ mod tests {
             header: vec![],
             data: make_strvec![ "value1", "value2", "this is a value" ],
             dims: (3,1),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -348,6 +1164,7 @@ mod tests {
             header: vec![],
             data: make_strvec![ "value1", "value2", "this is a value" ],
             dims: (3,1),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -365,6 +1182,7 @@ mod tests {
             header: vec![],
             data: make_strvec![ "value1", "value2", "this is a value" ],
             dims: (3,1),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -385,6 +1203,7 @@ mod tests {
                                 "value3", "value4", "another value",
                                 "value5", "value6", "yet another value" ],
             dims: (3,3),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -405,6 +1224,7 @@ mod tests {
                                 "value3", "value4", "another value",
                                 "value5", "value6", "yet another value" ],
             dims: (3,3),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -422,6 +1242,7 @@ mod tests {
             header: make_strvec![ "Name", "Type", "Value" ],
             data: make_strvec![ "value1", "int", "30" ],
             dims: (3, 1),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -439,6 +1260,7 @@ mod tests {
             header: make_strvec![ "Name", "Type", "Value" ],
             data: make_strvec![ "value1", "int", "30" ],
             dims: (3, 1),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -457,6 +1279,7 @@ mod tests {
             data: make_strvec![ "value1", "int", "30",
                                 "value2", "string", "this is a value" ],
             dims: (3,2),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -475,6 +1298,7 @@ mod tests {
             data: make_strvec![ "value1", "int", "30",
                                 "value2", "string", "this is a value" ],
             dims: (3,2),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -493,6 +1317,7 @@ mod tests {
             data: make_strvec![ "value1", "int", "30",
                                 "value2", "string", "this is a value" ],
             dims: (3,2),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -508,8 +1333,9 @@ mod tests {
 
         let expected = TableData {
             header: make_strvec![ "Name", "Type", "Value" ],
-            data: make_strvec![ "value1", "string", "thisis a value" ],
+            data: make_strvec![ "value1", "string", "this\nis a value" ],
             dims: (3, 1),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -518,6 +1344,56 @@ mod tests {
         assert_eq!(r.data, expected.data)
     }
 
+    #[test]
+    fn test_parse_csv_quoted_string_keeps_blank_lines() {
+        let s = "Name,Comment\nvalue1,\"first\n\nthird\n\"\n\nvalue2,\"\n\"\n";
+        let r = parse_values(&s, ',', true).unwrap();
+
+        assert_eq!(r.data, make_strvec![ "value1", "first\n\nthird\n", "value2", "\n" ]);
+    }
+
+    #[test]
+    fn test_parse_csv_quoted_string_keeps_crlf() {
+        let s = "Name,Address\r\nvalue1,\"1 Street\r\nTown\"\r\n";
+        let r = parse_values(&s, ',', true).unwrap();
+
+        assert_eq!(r.data, make_strvec![ "value1", "1 Street\r\nTown" ]);
+
+        let d = Dialect { header: true, normalize_newlines: true, .. Dialect::csv() };
+        let r = from_reader(s.as_bytes(), &d).unwrap().unwrap();
+
+        assert_eq!(r.data, make_strvec![ "value1", "1 Street\nTown" ]);
+    }
+
+    #[test]
+    fn test_parse_csv_cr_line_endings() {
+        let s = "Name,Value\rvalue1,10\rvalue2,\"a\rb\"\r";
+        let r = parse_values(&s, ',', true).unwrap();
+
+        assert_eq!(r.header, make_strvec![ "Name", "Value" ]);
+        assert_eq!(r.data, make_strvec![ "value1", "10", "value2", "a\rb" ]);
+        assert_eq!(r.line_ending(), Some(LineEnding::Cr));
+    }
+
+    #[test]
+    fn test_parse_csv_mixed_line_endings() {
+        let s = "Name,Value\r\nvalue1,10\rvalue2,20\nvalue3,30\r\n";
+        let r = parse_values(&s, ',', true).unwrap();
+
+        assert_eq!(r.data, make_strvec![ "value1", "10", "value2", "20", "value3", "30" ]);
+        assert_eq!(r.line_endings, LineEndings { lf: 1, crlf: 2, cr: 1 });
+        assert_eq!(r.line_ending(), Some(LineEnding::Mixed));
+        assert_eq!(r.line_endings.dominant(), Some("\r\n"));
+    }
+
+    #[test]
+    fn test_parse_csv_line_ending_styles() {
+        assert_eq!(parse_values("a,b\nc,d\n", ',', false).unwrap().line_ending(), Some(LineEnding::Lf));
+        assert_eq!(parse_values("a,b\r\nc,d", ',', false).unwrap().line_ending(), Some(LineEnding::CrLf));
+        assert_eq!(parse_values("a,b\rc,d\r", ',', false).unwrap().line_ending(), Some(LineEnding::Cr));
+        assert_eq!(parse_values("a,b", ',', false).unwrap().line_ending(), None);
+    }
+
     #[test]
     fn test_parse_csv_header_data_escaped_quoted_string() {
         let s = "Name,Type,Value\nvalue1,string,\"this \"\"is a value\"";
@@ -527,6 +1403,7 @@ mod tests {
             header: make_strvec![ "Name", "Type", "Value" ],
             data: make_strvec![ "value1", "string", "this \"is a value" ],
             dims: (3,1),
+            .. TableData::new()
         };
 
         let r = r.unwrap();
@@ -539,7 +1416,8 @@ mod tests {
     fn test_parse_csv_header_data_invalid_row_lengths() {
         let s = "Name,Type,Value\nvalue1,string";
         let r = parse_values(&s, ',', true);
-        let e = TableDataValidationError::RowFieldCountMismatchError { row: 1, expected: 3, found: 2};
+        let e = TableDataValidationError::RowFieldCountMismatchError { row: 1, expected: 3, found: 2,
+            location: location(2, 29, 14, "value1,string\n             ^") };
 
         assert_eq!(r.err().unwrap(), e);
     }
@@ -548,7 +1426,8 @@ mod tests {
     fn test_parse_csv_header_data_invalid_row_lengths2() {
         let s = "Name,Type,Value\nvalue1,string\nvalue2,int,30";
         let r = parse_values(&s, ',', true);
-        let e = TableDataValidationError::RowFieldCountMismatchError { row: 1, expected: 3, found: 2};
+        let e = TableDataValidationError::RowFieldCountMismatchError { row: 1, expected: 3, found: 2,
+            location: location(2, 29, 14, "value1,string\n             ^") };
 
         assert_eq!(r.err().unwrap(), e);
     }
@@ -557,7 +1436,8 @@ mod tests {
     fn test_parse_csv_header_data_invalid_row_lengths3() {
         let s = "Name,Type\nvalue1,string,abc";
         let r = parse_values(&s, ',', true);
-        let e = TableDataValidationError::RowFieldCountMismatchError { row: 1, expected: 2, found: 3};
+        let e = TableDataValidationError::RowFieldCountMismatchError { row: 1, expected: 2, found: 3,
+            location: location(2, 27, 18, "value1,string,abc\n                 ^") };
 
         assert_eq!(r.err().unwrap(), e);
     }
@@ -569,7 +1449,8 @@ mod tests {
 
         let e = TableDataValidationError::QuoteValidationError {
             subtype: QuoteValidationError::InvalidEscapeError,
-            row: 1, col: 3, value: String::from("a\"\"bc") };
+            row: 1, col: 3, value: String::from("a\"\"bc"),
+            location: location(2, 31, 16, "value1,string,a\"\"bc\n               ^") };
 
         assert_eq!(r.err().unwrap(), e);
     }
@@ -581,7 +1462,9 @@ mod tests {
 
         let e = TableDataValidationError::QuoteValidationError {
             subtype: QuoteValidationError::UnterminatedQuoteError,
-            row: 1, col: 3, value: String::from("\"a\"bc\"") };
+            row: 1, col: 3, value: String::from("\"a\"bc\""),
+            // the last quote opened a quoted section that never ends
+            location: location(2, 35, 20, "value1,string,\"a\"bc\"\n                   ^") };
 
         assert_eq!(r.err().unwrap(), e);
     }
@@ -593,7 +1476,8 @@ mod tests {
 
         let e = TableDataValidationError::QuoteValidationError {
             subtype: QuoteValidationError::UnterminatedQuoteError,
-            row: 1, col: 1, value: String::from("\"value1,string,abc") };
+            row: 1, col: 1, value: String::from("\"value1,string,abc"),
+            location: location(2, 16, 1, "\"value1,string,abc\n^") };
 
         assert_eq!(r.err().unwrap(), e);
     }
@@ -604,12 +1488,383 @@ mod tests {
         let r = parse_values(&s, ',', true);
 
         let m =
-            "At row 1. Unterminated outer quote error \
-            in column: 1, value: \"value1,string,abc";
+            "At row 1 (line 2, column 1, byte 16). Unterminated outer quote error \
+            in column: 1, value: \"value1,string,abc\n\
+            \"value1,string,abc\n\
+            ^";
 
         assert_eq!(r.err().map(|e| format!("{}",e)).unwrap(), m);
     }
 
+    #[test]
+    fn test_error_location_multi_byte_and_multi_line() {
+        let s = "Name,Value\r\n\"héllo\r\nwörld\",\"x\"y\"z\"\r\n";
+        let e = parse_values(&s, ',', true).err().unwrap();
+
+        // the stray quote is on the line after the start of the record
+        assert_eq!(e.position(), (1, 2));
+        assert_eq!(e.location(), &location(3, 31, 10, "wörld\",\"x\"y\"z\"\n         ^"));
+    }
+
+    #[test]
+    fn test_error_location_earlier_line_of_field() {
+        let s = "a,b\n\"x\"y\nz\",c\n";
+        let e = parse_values(&s, ',', false).err().unwrap();
+
+        assert_eq!(e.location(), &location(2, 6, 3, "\"x\"y\n  ^"));
+    }
+
+    #[test]
+    fn test_error_display_multi_byte_value() {
+        let value = "ü".repeat(70) + "\"";
+        let s = format!("a,b\n{},c\n", value);
+        let m = format!("{}", parse_values(&s, ',', false).err().unwrap());
+
+        assert!(m.starts_with(&format!("At row 2 (line 2, column 71, byte 144). Unterminated outer quote error in column: 1, value: {}\n",
+                                       "ü".repeat(64))));
+        assert!(m.ends_with(&format!("\n{}^", " ".repeat(30))));
+    }
+
+    #[test]
+    fn test_records_yields_one_record_at_a_time() {
+        let s = "Name,Type\nvalue1,int\nvalue2,\"a, b\"\n";
+        let mut r = Records::new(s.as_bytes(), &Dialect { header: true, .. Dialect::csv() });
+
+        assert_eq!(r.next(), Some(Ok(make_strvec![ "Name", "Type" ])));
+        assert_eq!(r.next(), Some(Ok(make_strvec![ "value1", "int" ])));
+        assert_eq!(r.next(), Some(Ok(make_strvec![ "value2", "a, b" ])));
+        assert_eq!(r.next(), None);
+    }
+
+    #[test]
+    fn test_records_source_lines() {
+        let s = "Name,Value\r\n\r\nvalue1,\"multi\r\nline\"\r\n# note\r\nvalue2,20";
+        let d = Dialect { header: true, comment: Some("#".to_owned()), .. Dialect::csv() };
+        let r = parse_dialect(&s, &d).unwrap();
+
+        assert_eq!(r.source(0), Some(RecordSource { line: 3, offset: 14 }));
+        assert_eq!(r.source(1), Some(RecordSource { line: 6, offset: 44 }));
+        assert_eq!(r.source(2), None);
+    }
+
+    #[test]
+    fn test_records_stops_after_error() {
+        let s = "a,b\nc\nd,e\n";
+        let mut r = Records::new(s.as_bytes(), &Dialect::csv());
+
+        assert_eq!(r.next(), Some(Ok(make_strvec![ "a", "b" ])));
+        assert_eq!(r.next(), Some(Err(TableDataValidationError::RowFieldCountMismatchError {
+            row: 2, expected: 2, found: 1, location: location(2, 5, 2, "c\n ^") })));
+        assert_eq!(r.next(), None);
+    }
+
+    #[test]
+    fn test_from_reader_small_buffer() {
+        let s = "Name,Value\nvalue1,\"multi\nline\"\nvalue2,20\n";
+        // A tiny buffer capacity forces records to span several reads
+        let br = BufReader::with_capacity(3, s.as_bytes());
+        let r = from_reader(br, &Dialect { header: true, .. Dialect::csv() }).expect("read error").expect("parse error");
+
+        assert_eq!(r.header(), &make_strvec![ "Name", "Value" ]);
+        assert_eq!(r.data(), &make_strvec![ "value1", "multi\nline", "value2", "20" ]);
+    }
+
+    #[test]
+    fn test_from_reader_invalid_utf8() {
+        let s: &[u8] = b"Name,Value\n\xff\xfe,1\n";
+        let r = from_reader(s, &Dialect { header: true, .. Dialect::csv() });
+
+        assert_eq!(r.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    fn parse_dialect(buffer: &str, dialect: &Dialect) -> TableResult<TableData> {
+        from_reader(buffer.as_bytes(), dialect).expect("reading from an in-memory buffer failed")
+    }
+
+    #[test]
+    fn test_parse_dialect_semicolon_single_quote() {
+        let s = "Name;Value\n'a;b';'it''s'\n";
+        let d = Dialect { delimiter: ';', quote: '\'', header: true, .. Dialect::csv() };
+        let r = parse_dialect(&s, &d).unwrap();
+
+        assert_eq!(r.header(), &make_strvec![ "Name", "Value" ]);
+        assert_eq!(r.data(), &make_strvec![ "a;b", "it's" ]);
+    }
+
+    #[test]
+    fn test_parse_dialect_pipe() {
+        let s = "a|b|c\r\nd|\"e|f\"|g\r\n";
+        let d = Dialect { delimiter: '|', .. Dialect::csv() };
+        let r = parse_dialect(&s, &d).unwrap();
+
+        assert_eq!(r.data(), &make_strvec![ "a", "b", "c", "d", "e|f", "g" ]);
+    }
+
+    #[test]
+    fn test_parse_dialect_escape_char() {
+        let s = "\"say \\\"hi\\\"\",a\\,b\n";
+        let d = Dialect { escape: Some('\\'), .. Dialect::csv() };
+        let r = parse_dialect(&s, &d).unwrap();
+
+        assert_eq!(r.data(), &make_strvec![ "say \"hi\"", "a,b" ]);
+    }
+
+    #[test]
+    fn test_parse_dialect_comment_lines() {
+        let s = "# exported data\nName,Value\n# a comment\nvalue1,10\n";
+        let d = Dialect { comment: Some("#".to_owned()), header: true, .. Dialect::csv() };
+        let r = parse_dialect(&s, &d).unwrap();
+
+        assert_eq!(r.header(), &make_strvec![ "Name", "Value" ]);
+        assert_eq!(r.data(), &make_strvec![ "value1", "10" ]);
+    }
+
+    #[test]
+    fn test_parse_dialect_trim() {
+        let s = " Name , Value \n value1 , \" 10 \" \n";
+        let d = Dialect { trim: Trim::All, header: true, .. Dialect::csv() };
+        let r = parse_dialect(&s, &d).unwrap();
+
+        assert_eq!(r.header(), &make_strvec![ "Name", "Value" ]);
+        assert_eq!(r.data(), &make_strvec![ "value1", " 10 " ]);
+
+        let d = Dialect { trim: Trim::Headers, header: true, .. Dialect::csv() };
+        let r = parse_dialect("  Name ,Value\n a ,b\n", &d).unwrap();
+
+        assert_eq!(r.header(), &make_strvec![ "Name", "Value" ]);
+        assert_eq!(r.data(), &make_strvec![ " a ", "b" ]);
+    }
+
+    #[test]
+    fn test_parse_dialect_custom_terminator() {
+        let s = "a,b;c,d;";
+        let d = Dialect { terminator: Terminator::Char(';'), .. Dialect::csv() };
+        let r = parse_dialect(&s, &d).unwrap();
+
+        assert_eq!(r.data(), &make_strvec![ "a", "b", "c", "d" ]);
+        assert_eq!(r.rows(), 2);
+    }
+
+    #[test]
+    fn test_parse_preamble() {
+        let s = "Bank export\r\nAccount: 1234\r\n\r\n# \"quoted\r\nDate,Amount\r\n# pending\r\n2023-01-02,10.5\r\n";
+        let d = Dialect { skip_lines: 1, skip_until: Some("^Date,".to_owned()), comment: Some("#".to_owned()),
+                          header: true, .. Dialect::csv() };
+        let r = parse_dialect(&s, &d).unwrap();
+
+        assert_eq!(r.preamble(), &make_strvec![ "Bank export", "Account: 1234", "", "# \"quoted" ]);
+        assert_eq!(r.header(), &make_strvec![ "Date", "Amount" ]);
+        assert_eq!(r.data(), &make_strvec![ "2023-01-02", "10.5" ]);
+        // rows are located in the whole input
+        assert_eq!(r.source(0), Some(RecordSource { line: 7, offset: 65 }));
+
+        // a '\r\n' split across reads still ends a single line
+        let br = io::BufReader::with_capacity(4, "one\r\ntwo\r\na,b\n".as_bytes());
+        let r = from_reader(br, &Dialect { skip_lines: 2, .. Dialect::csv() }).unwrap().unwrap();
+
+        assert_eq!(r.preamble(), &make_strvec![ "one", "two" ]);
+        assert_eq!(r.data(), &make_strvec![ "a", "b" ]);
+
+        let d = Dialect { skip_until: Some("[".to_owned()), .. Dialect::csv() };
+        assert_eq!(from_reader("a,b\n".as_bytes(), &d).err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+    }
+
+    #[test]
+    fn test_parse_whitespace_separator() {
+        let s = "  PID TTY          TIME CMD\n\t 1042 pts/0    00:00:00 \"bash -l\"  \n   \n 1337 pts/0    00:00:01 ps\n";
+        let d = Dialect { separator: Separator::Whitespace, header: true, .. Dialect::csv() };
+        let r = parse_dialect(&s, &d).unwrap();
+
+        assert_eq!(r.header(), &make_strvec![ "PID", "TTY", "TIME", "CMD" ]);
+        assert_eq!(r.data(), &make_strvec![ "1042", "pts/0", "00:00:00", "bash -l", "1337", "pts/0", "00:00:01", "ps" ]);
+
+        // a wrong number of fields is reported where the row ends
+        let e = TableDataValidationError::RowFieldCountMismatchError { row: 1, expected: 2, found: 3,
+            location: location(2, 10, 6, "a b c\n     ^") };
+        assert_eq!(parse_dialect("x  y\na b c\n", &d).err().unwrap(), e);
+    }
+
+    #[test]
+    fn test_parse_text_separator() {
+        let d = Dialect { separator: Separator::Text("||".to_owned()), escape: Some('\\'), .. Dialect::csv() };
+        let r = parse_dialect("a||b|c||\"d||e\"\nf\\||g||h||i\n", &d).unwrap();
+
+        assert_eq!(r.data(), &make_strvec![ "a", "b|c", "d||e", "f||g", "h", "i" ]);
+
+        let d = Dialect { separator: Separator::Text("::".to_owned()), header: true, .. Dialect::csv() };
+        let r = parse_dialect("key::value\nhost::example.org:8080\n", &d).unwrap();
+
+        assert_eq!(r.header(), &make_strvec![ "key", "value" ]);
+        assert_eq!(r.data(), &make_strvec![ "host", "example.org:8080" ]);
+    }
+
+    #[test]
+    fn test_parse_regex_separator() {
+        let s = "name ; value;note\r\n\r\n\"a\";b  ;   c\r\n";
+        let d = Dialect { separator: Separator::Regex(r"\s*;\s*".to_owned()), header: true, .. Dialect::csv() };
+        let r = parse_dialect(&s, &d).unwrap();
+
+        // quotes are not special
+        assert_eq!(r.header(), &make_strvec![ "name", "value", "note" ]);
+        assert_eq!(r.data(), &make_strvec![ "\"a\"", "b", "c" ]);
+        assert_eq!(r.dims, (3, 1));
+    }
+
+    #[test]
+    fn test_parse_invalid_separator() {
+        let d = Dialect { separator: Separator::Regex("(".to_owned()), .. Dialect::csv() };
+        let mut r = Records::new("a,b\n".as_bytes(), &d);
+
+        assert!(matches!(r.next(), Some(Err(TableDataValidationError::ReadError {
+            row: 1, kind: io::ErrorKind::InvalidInput, ..
+        }))));
+        assert!(r.next().is_none());
+
+        // the error reaches the caller of from_reader as an io::Error
+        let d = Dialect { separator: Separator::Text(String::new()), .. Dialect::csv() };
+        let e = from_reader("a,b\n".as_bytes(), &d).err().map(|e| e.kind());
+        assert_eq!(e, Some(io::ErrorKind::InvalidInput));
+    }
+
+    #[test]
+    fn test_parse_invalid_dialect() {
+        let d = Dialect { quote: ',', .. Dialect::csv() };
+        let mut r = Records::new("a,b\n".as_bytes(), &d);
+
+        assert!(matches!(r.next(), Some(Err(TableDataValidationError::ReadError {
+            row: 1, kind: io::ErrorKind::InvalidInput, ..
+        }))));
+        assert!(r.next().is_none());
+
+        let d = Dialect { escape: Some('\n'), .. Dialect::csv() };
+        assert_eq!(d.validate().err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+
+        let d = Dialect { escape: Some('\\'), terminator: Terminator::Char(';'), .. Dialect::csv() };
+        assert!(d.validate().is_ok());
+    }
+
+    fn lenient() -> Dialect {
+        Dialect { header: true, lenient: true, .. Dialect::csv() }
+    }
+
+    #[test]
+    fn test_parse_lenient_pads_short_rows() {
+        let r = parse_dialect("Name,Type,Value\nvalue1,string\nvalue2,int,30\n", &lenient()).unwrap();
+
+        assert_eq!(r.dims, (3, 2));
+        assert_eq!(r.data(), &make_strvec![ "value1", "string", "", "value2", "int", "30" ]);
+        assert_eq!(r.errors(), &vec![ TableDataValidationError::RowFieldCountMismatchError {
+            row: 1, expected: 3, found: 2, location: location(2, 29, 14, "value1,string\n             ^") } ]);
+    }
+
+    #[test]
+    fn test_parse_lenient_overflow_columns() {
+        let r = parse_dialect("Name,Type\nvalue1,string,abc\nvalue2,int\n", &lenient()).unwrap();
+
+        assert_eq!(r.header(), &make_strvec![ "Name", "Type", "" ]);
+        assert_eq!(r.data(), &make_strvec![ "value1", "string", "abc", "value2", "int", "" ]);
+        // the second row is compared against the first record and not the widened table
+        assert_eq!(r.errors().len(), 1);
+        assert_eq!(r.errors()[0].position(), (1, 3));
+    }
+
+    #[test]
+    fn test_parse_lenient_stray_quotes() {
+        let s = "Name,Value\nvalue1,a\"\"bc\nvalue2,5\" screen\nvalue3,\"a \"\"b\"\"\"\n";
+        let r = parse_dialect(&s, &lenient()).unwrap();
+
+        assert_eq!(r.data(), &make_strvec![ "value1", "a\"\"bc", "value2", "5\" screen", "value3", "a \"b\"" ]);
+        assert_eq!(r.errors(), &vec![
+            TableDataValidationError::QuoteValidationError {
+                subtype: QuoteValidationError::InvalidEscapeError, row: 1, col: 2,
+                value: String::from("a\"\"bc"), location: location(2, 19, 9, "value1,a\"\"bc\n        ^") },
+            TableDataValidationError::QuoteValidationError {
+                subtype: QuoteValidationError::InvalidQuoteError, row: 2, col: 2,
+                value: String::from("5\" screen"), location: location(3, 32, 9, "value2,5\" screen\n        ^") } ]);
+    }
+
+    #[test]
+    fn test_parse_lenient_unterminated_quote() {
+        let r = parse_dialect("Name,Type,Value\n\"value1,string,abc\n", &lenient()).unwrap();
+
+        assert_eq!(r.data(), &make_strvec![ "\"value1,string,abc\n", "", "" ]);
+        assert_eq!(r.errors().len(), 1);
+        assert_eq!(r.errors()[0].position(), (1, 1));
+    }
+
+    #[test]
+    fn test_parse_lenient_collects_every_error() {
+        let s = "a,b\nc\nd,e,f\n\"g\"h\",i\nj,k\n";
+        let r = parse_dialect(&s, &Dialect { header: false, .. lenient() }).unwrap();
+
+        assert_eq!(r.rows(), 5);
+        assert_eq!(r.errors().iter().map(|e| e.position()).collect::<Vec<_>>(),
+                   vec![ (2, 2), (3, 3), (4, 1) ]);
+    }
+
+    #[test]
+    fn test_from_file_encoded_utf16_with_bom() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("Näme,Value\nä,1\n".encode_utf16().flat_map(|u| u.to_le_bytes().to_vec()));
+
+        let f = "csv_data_utf16.csv";
+        File::create(&f).and_then(|mut f| f.write_all(&bytes)).expect("setup failed");
+
+        let d = Dialect { header: true, .. Dialect::csv() };
+        let (r, e) = from_file_encoded(&f, &d, None).expect("file read error");
+        let r = r.expect("parse error");
+
+        assert_eq!(e, DetectedEncoding { encoding: Encoding::Utf16Le, bom: true });
+        assert_eq!(r.header(), &make_strvec![ "Näme", "Value" ]);
+        assert_eq!(r.data(), &make_strvec![ "ä", "1" ]);
+
+        teardown_from_file(&f).expect("teardown failed");
+    }
+
+    #[test]
+    fn test_from_file_gzip() {
+        // "Näme,Value\nä,1\n" in a stored deflate block
+        let bytes = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x04\x03\x01\x11\x00\xee\xff\
+                      N\xc3\xa4me,Value\n\xc3\xa4,1\n\x1b\xf0\x87\x04\x11\x00\x00\x00";
+
+        let f = "csv_data_gzip.csv.gz";
+        File::create(&f).and_then(|mut f| f.write_all(bytes)).expect("setup failed");
+
+        let d = Dialect { header: true, .. Dialect::csv() };
+        let (r, e) = from_file_encoded(&f, &d, None).expect("file read error");
+        let r = r.expect("parse error");
+
+        assert_eq!(e, DetectedEncoding { encoding: Encoding::Utf8, bom: false });
+        assert_eq!(r.header(), &make_strvec![ "Näme", "Value" ]);
+        assert_eq!(r.data(), &make_strvec![ "ä", "1" ]);
+
+        teardown_from_file(&f).expect("teardown failed");
+    }
+
+    #[test]
+    fn test_from_read() {
+        // "Näme\tValue\nä\t1\n" in a stored deflate block, as a pipeline may send it
+        let bytes: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x04\x03\x01\x11\x00\xee\xff\
+                             N\xc3\xa4me\tValue\n\xc3\xa4\t1\n\xf8\xd8\x93\xba\x11\x00\x00\x00";
+
+        let d = Dialect { header: true, .. Dialect::tsv() };
+        let (r, e) = from_read(bytes, &d, None).expect("read error");
+        let r = r.expect("parse error");
+
+        assert_eq!(e, DetectedEncoding { encoding: Encoding::Utf8, bom: false });
+        assert_eq!(r.header(), &make_strvec![ "Näme", "Value" ]);
+        assert_eq!(r.data(), &make_strvec![ "ä", "1" ]);
+    }
+
+    #[test]
+    fn test_is_stream() {
+        assert!(is_stdin(STDIN_FILENAME));
+        assert!(is_stream(STDIN_FILENAME));
+        assert!(!is_stream("Cargo.toml"));
+        assert!(!is_stream("src"));
+        assert!(!is_stream("missing.csv"));
+    }
+
     // helpers for testing from_file(...)
     fn setup_from_file(target: &str, data: &str) -> io::Result<()> {
         let mut f = File::create(target)?;
@@ -639,7 +1894,7 @@ mod tests {
                 "value1", "10", "int",
                 "value2", "20", "int",
                 "value3", "40.5", "float",
-                "value4", "a value, is it not?", "string",
+                "val\nue4", "a value, is it not?", "string",
                 "value5", "this is a \"quoted\" word", "string" ];
 
         let dims_expected = (3, 5);
@@ -677,7 +1932,8 @@ mod tests {
             subtype: QuoteValidationError::InvalidQuoteError,
             row: 4,
             col: 1,
-            value: "\"value4,\"a value".to_owned()
+            value: "\"val\nue4,\"a value".to_owned(),
+            location: location(6, 71, 5, "ue4,\"a value, is it not?\",string\n    ^")
         };
 
         assert_eq!(r.err().unwrap(), e);
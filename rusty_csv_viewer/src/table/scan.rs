@@ -0,0 +1,251 @@
+use crate::table::dialect::{Dialect, Separator, Terminator};
+
+/// Where a record starts in the input
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct RecordStart {
+    pub offset: usize,
+    /// 1-based physical line
+    pub line: usize,
+}
+
+/// Finds where records start by following quotes and terminators byte by byte
+///
+/// This is much faster than parsing as no fields are built, only counted from the
+/// delimiters outside quotes. Blank lines and comment lines between records are skipped. Quotes toggle a quoted section the same way the parser
+/// does for the dialect, so in lenient mode a stray quote can not swallow the rest of the input.
+///
+/// The delimiter, quote, escape and terminator must be ASCII, see `can_scan`
+pub struct RecordStarts<'a> {
+    data: &'a [u8],
+    pos: usize,
+    start: usize,
+    delimiter: u8,
+    quote: u8,
+    escape: Option<u8>,
+    terminator: Option<u8>,
+    comment: Option<&'a [u8]>,
+    lenient: bool,
+    line: usize,
+    in_record: bool,
+    in_comment: bool,
+    inside_quote: bool,
+    escaped: bool,
+    /// The field has only had whitespace so far
+    field_start: bool,
+    /// The previous byte closed a quoted section so another quote is a doubled quote
+    after_quote: bool,
+    /// Fields of the record being scanned so far
+    record_fields: usize,
+    /// Fields of the last record that ended
+    fields: usize,
+}
+
+/// Test whether the input of a dialect can be scanned as bytes
+///
+/// Only fields separated by the delimiter character of a valid dialect are scanned. A
+/// preamble is skipped by the parser as its lines could hold unbalanced quotes
+pub fn can_scan(dialect: &Dialect) -> bool {
+    if dialect.separator != Separator::Delimiter || dialect.has_preamble() || dialect.validate().is_err() {
+        return false;
+    }
+
+    let chars = [Some(dialect.delimiter), Some(dialect.quote), dialect.escape, Some(dialect.terminator_char())];
+
+    chars.iter().flatten().all(|c| c.is_ascii())
+}
+
+impl<'a> RecordStarts<'a> {
+    /// Scan UTF-8 input from a byte offset that is at the start of a line
+    pub fn new(data: &'a [u8], start: usize, dialect: &'a Dialect) -> Self {
+        RecordStarts {
+            data,
+            pos: start,
+            start,
+            delimiter: dialect.delimiter as u8,
+            quote: dialect.quote as u8,
+            escape: dialect.escape.filter(|e| *e != dialect.quote).map(|e| e as u8),
+            terminator: match dialect.terminator {
+                Terminator::Default => None,
+                Terminator::Char(t) => Some(t as u8),
+            },
+            comment: dialect.comment.as_ref().filter(|c| !c.is_empty()).map(|c| c.as_bytes()),
+            lenient: dialect.lenient,
+            line: 1,
+            in_record: false,
+            in_comment: false,
+            inside_quote: false,
+            escaped: false,
+            field_start: true,
+            after_quote: false,
+            record_fields: 0,
+            fields: 0,
+        }
+    }
+
+    /// Number of fields of the record before the one last returned, or of the last
+    /// record once the input has ended
+    pub fn fields(&self) -> usize {
+        self.fields
+    }
+
+    fn is_terminator(&self, b: u8) -> bool {
+        match self.terminator {
+            None => b == b'\n' || b == b'\r',
+            Some(t) => b == t,
+        }
+    }
+
+    /// Test whether a byte starts a physical line. A '\r\n' is a single line break
+    fn is_line_start(&self, i: usize) -> bool {
+        i == self.start || match self.data[i-1] {
+            b'\n' => true,
+            b'\r' => self.data[i] != b'\n',
+            _ => false
+        }
+    }
+
+    /// Follow the quoting state of a byte inside a record
+    fn process(&mut self, b: u8) {
+        if self.escaped || Some(b) == self.escape {
+            self.escaped = !self.escaped;
+            self.field_start = false;
+            self.after_quote = false;
+        } else if b == self.quote {
+            if self.inside_quote {
+                self.inside_quote = false;
+                self.after_quote = true;
+            } else {
+                self.inside_quote = !self.lenient || self.field_start || self.after_quote;
+                self.after_quote = false;
+            }
+            self.field_start = false;
+        } else if !self.inside_quote {
+            self.after_quote = false;
+
+            if b == self.delimiter {
+                self.field_start = true;
+                self.record_fields += 1;
+            } else if self.is_terminator(b) {
+                self.in_record = false;
+            } else if b != b' ' && b != b'\t' {
+                self.field_start = false;
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for RecordStarts<'a> {
+    type Item = RecordStart;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.data.len() {
+            let i = self.pos;
+            let b = self.data[i];
+            let line_start = self.is_line_start(i);
+
+            self.pos += 1;
+
+            if line_start {
+                if i > self.start {
+                    self.line += 1;
+                }
+                self.in_comment = false;
+            }
+
+            if self.in_comment {
+                continue;
+            }
+
+            let mut found = None;
+
+            if !self.in_record {
+                if self.is_terminator(b) {
+                    continue;
+                }
+
+                if line_start && self.comment.map_or(false, |c| self.data[i..].starts_with(c)) {
+                    self.in_comment = true;
+                    continue;
+                }
+
+                self.in_record = true;
+                self.field_start = true;
+                self.after_quote = false;
+                self.fields = self.record_fields;
+                self.record_fields = 1;
+                found = Some(RecordStart { offset: i, line: self.line });
+            }
+
+            self.process(b);
+
+            if found.is_some() {
+                return found;
+            }
+        }
+
+        self.fields = self.record_fields;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(data: &[u8], dialect: &Dialect) -> Vec<usize> {
+        RecordStarts::new(data, 0, dialect).map(|r| r.offset).collect()
+    }
+
+    #[test]
+    fn test_record_starts() {
+        let s = b"a,b\r\n\r\n\"c\r\nd\",e\r\n# note\nf,\"g\"\"h\"\n";
+        let d = Dialect { comment: Some("#".to_owned()), .. Dialect::csv() };
+        let r = RecordStarts::new(s, 0, &d).collect::<Vec<_>>();
+
+        assert_eq!(r, vec![ RecordStart { offset: 0, line: 1 },
+                            RecordStart { offset: 7, line: 3 },
+                            RecordStart { offset: 24, line: 6 } ]);
+    }
+
+    #[test]
+    fn test_record_starts_stray_quote() {
+        let s = b"a,5\" screen\nb,c\n\"d\"\n";
+
+        // a strict parser takes the quote as the start of a quoted section
+        assert_eq!(offsets(s, &Dialect::csv()), vec![ 0 ]);
+        assert_eq!(offsets(s, &Dialect { lenient: true, .. Dialect::csv() }), vec![ 0, 12, 16 ]);
+    }
+
+    #[test]
+    fn test_record_starts_escape_and_terminator() {
+        let s = b"a\\;b,c;d,e;";
+        let d = Dialect { escape: Some('\\'), terminator: Terminator::Char(';'), .. Dialect::csv() };
+
+        assert_eq!(offsets(s, &d), vec![ 0, 7 ]);
+    }
+
+    #[test]
+    fn test_record_starts_fields() {
+        let s = b"a,\"b,c\"\n\nd,e,f\ng";
+        let d = Dialect::csv();
+        let mut r = RecordStarts::new(s, 0, &d);
+        let mut fields = Vec::new();
+
+        while r.next().is_some() {
+            fields.push(r.fields());
+        }
+        fields.push(r.fields());
+
+        // the count before the first record is 0
+        assert_eq!(fields, vec![ 0, 2, 3, 1 ]);
+    }
+
+    #[test]
+    fn test_can_scan() {
+        assert_eq!(can_scan(&Dialect::csv()), true);
+        assert_eq!(can_scan(&Dialect { delimiter: '§', .. Dialect::csv() }), false);
+        assert_eq!(can_scan(&Dialect { separator: Separator::Whitespace, .. Dialect::csv() }), false);
+        assert_eq!(can_scan(&Dialect { skip_lines: 2, .. Dialect::csv() }), false);
+        assert_eq!(can_scan(&Dialect { quote: ',', .. Dialect::csv() }), false);
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+
+use crate::table::dialect::Dialect;
+use crate::table::encoding::DecodeReader;
+use crate::table::header::detect_header;
+use crate::table::reader::{open_file, Records};
+
+/// Number of bytes sampled from the start of the input
+pub const SNIFF_SAMPLE_SIZE: usize = 64 * 1024;
+/// Maximum number of records that are scored
+const SNIFF_MAX_RECORDS: usize = 200;
+/// Share of the records after a preamble that must have the same field count for the
+/// preamble to be offered
+const PREAMBLE_MIN_CONSISTENCY: f32 = 0.9;
+
+const DELIMITER_CANDIDATES: [char; 5] = [',', '\t', ';', '|', ':'];
+const QUOTE_CANDIDATES: [char; 2] = ['"', '\''];
+
+/// A dialect guessed from a sample of the input
+///
+/// The confidence is between 0.0 (no idea) and 1.0 (every sampled record agrees)
+#[derive(Debug,Clone,PartialEq)]
+pub struct Sniffed {
+    pub dialect: Dialect,
+    pub confidence: f32,
+    /// The dialect skipping the lines before the table when there seem to be some, such
+    /// as the title and account lines at the top of a bank export. The lines could also
+    /// be part of the table so the caller should offer it rather than use it unasked
+    pub skip_preamble: Option<Dialect>,
+}
+
+/// Sniff the dialect of a file from its first SNIFF_SAMPLE_SIZE decoded bytes
+pub fn sniff_file(filename: &str) -> io::Result<Sniffed> {
+    let decoder = DecodeReader::new(open_file(filename)?, None)?;
+    let (sniffed, _) = sniff_reader(BufReader::new(decoder), SNIFF_SAMPLE_SIZE)?;
+    Ok(sniffed)
+}
+
+/// Sniff the dialect from the start of a reader
+///
+/// Returns the result and a reader that replays the sample before the rest of the input
+/// so streams such as stdin can still be read in full
+pub fn sniff_reader<R: BufRead>(mut reader: R, sample_size: usize)
+    -> io::Result<(Sniffed, io::Chain<Cursor<Vec<u8>>, R>)>
+{
+    let mut sample = Vec::with_capacity(sample_size);
+    reader.by_ref().take(sample_size as u64).read_to_end(&mut sample)?;
+
+    let truncated = sample.len() == sample_size;
+    let sniffed = {
+        let text = String::from_utf8_lossy(&sample);
+        sniff(&text, truncated)
+    };
+
+    Ok((sniffed, Cursor::new(sample).chain(reader)))
+}
+
+/// Guess the delimiter, quote character, preamble and header presence of a sample
+///
+/// Every candidate dialect is scored by how consistent the field counts of the
+/// sampled records are. If the sample was cut off the last partial record is ignored.
+/// A preamble is only guessed when nearly every record after it has the same field count
+pub fn sniff(sample: &str, truncated: bool) -> Sniffed {
+    let sample = if truncated {
+        // drop the incomplete record at the end of the sample
+        match sample.rfind(|c| c == '\n' || c == '\r') {
+            Some(i) => &sample[..=i],
+            None => sample
+        }
+    } else {
+        sample
+    };
+
+    let mut best: Option<(f32, Dialect)> = None;
+
+    for delimiter in DELIMITER_CANDIDATES.iter() {
+        for quote in QUOTE_CANDIDATES.iter() {
+            let score = score_candidate(sample, *delimiter, *quote, truncated);
+
+            // candidates earlier in the list win ties
+            if score > best.as_ref().map_or(0.0, |b| b.0) {
+                let dialect = Dialect { delimiter: *delimiter, quote: *quote, .. Dialect::csv() };
+                best = Some((score, dialect));
+            }
+        }
+    }
+
+    match best {
+        Some((confidence, mut dialect)) => {
+            dialect.header = sniff_header(sample, &dialect);
+
+            let skip_preamble = match sniff_preamble(sample, &dialect) {
+                0 => None,
+                lines => {
+                    let mut skipping = Dialect { skip_lines: lines, .. dialect.clone() };
+                    skipping.header = sniff_header(sample, &skipping);
+                    Some(skipping)
+                }
+            };
+
+            Sniffed { dialect, confidence, skip_preamble }
+        },
+        None => Sniffed { dialect: Dialect::csv(), confidence: 0.0, skip_preamble: None }
+    }
+}
+
+/// Score a delimiter and quote character pair between 0.0 and 1.0
+///
+/// The score is the share of records that have the most common field count, scaled by
+/// how many quote characters sit on a field boundary. A single column scores 0.0.
+fn score_candidate(sample: &str, delimiter: char, quote: char, truncated: bool) -> f32 {
+    let mut counts: Vec<usize> = Vec::new();
+    let mut fields = 1;
+    let mut record_len = 0;
+    let mut inside_quote = false;
+    let mut quotes = 0;
+    let mut boundary_quotes = 0;
+    let mut prev = '\n';
+    let mut chars = sample.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if counts.len() >= SNIFF_MAX_RECORDS {
+            break;
+        }
+
+        let next = chars.peek().copied().unwrap_or('\n');
+
+        if c == quote {
+            quotes += 1;
+
+            let at_start = prev == delimiter || prev == '\n' || prev == '\r';
+            let at_end = next == delimiter || next == '\n' || next == '\r';
+
+            if at_start || at_end || prev == quote || next == quote {
+                boundary_quotes += 1;
+            }
+
+            inside_quote = !inside_quote;
+        } else if !inside_quote {
+            if c == delimiter {
+                fields += 1;
+            } else if c == '\n' || c == '\r' {
+                // skip blank lines and the second half of a crlf
+                if record_len > 0 {
+                    counts.push(fields);
+                }
+
+                fields = 1;
+                record_len = 0;
+                prev = c;
+                continue;
+            }
+        }
+
+        record_len += 1;
+        prev = c;
+    }
+
+    if record_len > 0 && (!inside_quote || !truncated) {
+        counts.push(fields);
+    }
+
+    // a quote left open in a complete sample means the quote character is wrong
+    if counts.is_empty() || (inside_quote && !truncated) {
+        return 0.0;
+    }
+
+    let mut frequency: HashMap<usize, usize> = HashMap::new();
+
+    for n in counts.iter() {
+        *frequency.entry(*n).or_insert(0) += 1;
+    }
+
+    // most common field count, preferring the wider one on ties
+    let (mode, mode_count) = frequency.iter()
+        .max_by_key(|(n, count)| (**count, **n))
+        .map(|(n, count)| (*n, *count))
+        .unwrap();
+
+    if mode < 2 {
+        return 0.0;
+    }
+
+    let consistency = mode_count as f32 / counts.len() as f32;
+    let quote_fitness = if quotes > 0 { boundary_quotes as f32 / quotes as f32 } else { 1.0 };
+
+    consistency * quote_fitness
+}
+
+/// Count the lines before the table of a sample, such as the title and account lines at
+/// the top of a bank export
+///
+/// The table starts at the first two records in a row that have the most common field
+/// count. A record of two or more fields that is one field short right before them is
+/// kept as the header, as written for tables with row names. Returns 0 unless at least
+/// PREAMBLE_MIN_CONSISTENCY of the records from the first of the two have that count
+fn sniff_preamble(sample: &str, dialect: &Dialect) -> usize {
+    let lenient = Dialect { lenient: true, .. dialect.clone() };
+    let mut records = Records::new(sample.as_bytes(), &lenient);
+    let mut starts: Vec<(usize, usize)> = Vec::new();
+
+    while let Some(Ok(fields)) = records.next() {
+        starts.push((records.record_source().line, fields.len()));
+
+        if starts.len() >= SNIFF_MAX_RECORDS {
+            break;
+        }
+    }
+
+    let mut frequency: HashMap<usize, usize> = HashMap::new();
+
+    for (_, n) in starts.iter() {
+        *frequency.entry(*n).or_insert(0) += 1;
+    }
+
+    let mode = match frequency.iter().max_by_key(|(n, count)| (**count, **n)) {
+        Some((n, _)) => *n,
+        None => return 0
+    };
+
+    let first = (0..starts.len())
+        .find(|i| starts[*i..].iter().take(2).all(|(_, n)| *n == mode))
+        .unwrap_or(0);
+
+    let rows = &starts[first..];
+    let consistent = rows.iter().filter(|(_, n)| *n == mode).count();
+
+    if (consistent as f32) < PREAMBLE_MIN_CONSISTENCY * rows.len() as f32 {
+        return 0;
+    }
+
+    let first = match first.checked_sub(1).map(|i| starts[i].1) {
+        Some(n) if n >= 2 && n + 1 == mode => first - 1,
+        _ => first
+    };
+
+    starts.get(first).map_or(0, |(line, _)| line - 1)
+}
+
+/// Guess whether the first record of a sample is a header
+fn sniff_header(sample: &str, dialect: &Dialect) -> bool {
+    let records = Records::new(sample.as_bytes(), dialect)
+        .take(SNIFF_MAX_RECORDS)
+        .take_while(|r| r.is_ok())
+        .map(|r| r.unwrap())
+        .collect::<Vec<_>>();
+
+    detect_header(&records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_comma() {
+        let s = "Name,Value,Type\nvalue1,10,int\nvalue2,\"a, b\",string\n";
+        let r = sniff(s, false);
+
+        assert_eq!(r.dialect.delimiter, ',');
+        assert_eq!(r.dialect.quote, '"');
+        assert_eq!(r.confidence, 1.0);
+    }
+
+    #[test]
+    fn test_sniff_tab() {
+        let s = "Name\tValue\nvalue1, more\t10\nvalue2\t20\n";
+        let r = sniff(s, false);
+
+        assert_eq!(r.dialect.delimiter, '\t');
+        assert_eq!(r.confidence, 1.0);
+    }
+
+    #[test]
+    fn test_sniff_semicolon_and_pipe() {
+        let s = "a;b;c\n1,5;2,5;3\n4;5;6\n";
+        assert_eq!(sniff(s, false).dialect.delimiter, ';');
+
+        let s = "id|name|note\n1|x|a, b\n2|y|c\n";
+        assert_eq!(sniff(s, false).dialect.delimiter, '|');
+    }
+
+    #[test]
+    fn test_sniff_single_quote() {
+        let s = "'a,b',c\n'd,e',f\n'g',h\n";
+        let r = sniff(s, false);
+
+        assert_eq!(r.dialect.delimiter, ',');
+        assert_eq!(r.dialect.quote, '\'');
+    }
+
+    #[test]
+    fn test_sniff_apostrophe_is_not_a_quote() {
+        let s = "name,comment\nbob,it's fine\nann,that's ok\n";
+        let r = sniff(s, false);
+
+        assert_eq!(r.dialect.quote, '"');
+        assert_eq!(r.confidence, 1.0);
+    }
+
+    #[test]
+    fn test_sniff_inconsistent_lowers_confidence() {
+        let s = "a,b,c\n1,2,3\n4,5\n6,7,8\n";
+        let r = sniff(s, false);
+
+        assert_eq!(r.dialect.delimiter, ',');
+        assert_eq!(r.confidence, 0.75);
+    }
+
+    #[test]
+    fn test_sniff_single_column() {
+        let r = sniff("value\nanother value\n", false);
+
+        assert_eq!(r.dialect, Dialect::csv());
+        assert_eq!(r.confidence, 0.0);
+    }
+
+    #[test]
+    fn test_sniff_truncated_sample() {
+        let s = "a|b\n1|2\n3|\"unfinished";
+        let r = sniff(s, true);
+
+        assert_eq!(r.dialect.delimiter, '|');
+        assert_eq!(r.confidence, 1.0);
+    }
+
+    #[test]
+    fn test_sniff_header() {
+        let r = sniff("Name,Value\nvalue1,10\nvalue2,20\n", false);
+        assert_eq!(r.dialect.header, true);
+
+        let r = sniff("value1,10\nvalue2,20\n", false);
+        assert_eq!(r.dialect.header, false);
+    }
+
+    #[test]
+    fn test_sniff_preamble() {
+        let s = "Bank export\nAccount: 1234\n\n\
+                 Date,Amount,Memo\n2023-01-02,10,rent\n2023-01-03,20,food\n";
+        let r = sniff(s, false);
+
+        // the preamble is only offered
+        assert_eq!(r.dialect.delimiter, ',');
+        assert_eq!(r.dialect.skip_lines, 0);
+
+        let skipping = r.skip_preamble.expect("no preamble");
+        assert_eq!((skipping.delimiter, skipping.skip_lines, skipping.header), (',', 3, true));
+
+        let r = sniff("Bank export\nAccount: 1234\nName;Value\nvalue1;10\n", false);
+        assert_eq!(r.skip_preamble.map(|d| d.skip_lines), Some(2));
+
+        let r = sniff("a,b,c\n1,2,3\n4,5\n6,7,8\n", false);
+        assert_eq!(r.skip_preamble, None);
+    }
+
+    #[test]
+    fn test_sniff_preamble_needs_consistent_table() {
+        let r = sniff("Title\na,b,c\n1,2,3\n4,5,6\n7,8,9\n", false);
+        assert_eq!(r.skip_preamble.map(|d| d.skip_lines), Some(1));
+
+        // a ragged table is not taken to start after a preamble
+        let r = sniff("Title\na,b,c\n1,2,3\n4,5\n7,8,9\n", false);
+        assert_eq!(r.skip_preamble, None);
+    }
+
+    #[test]
+    fn test_sniff_preamble_keeps_row_names_header() {
+        let r = sniff("x,y\nr1,1,2\nr2,3,4\nr3,5,6\n", false);
+        assert_eq!(r.skip_preamble, None);
+
+        let r = sniff("sep=,\nx,y\nr1,1,2\nr2,3,4\n", false);
+        assert_eq!(r.skip_preamble.map(|d| d.skip_lines), Some(1));
+    }
+
+    #[test]
+    fn test_sniff_reader_replays_sample() {
+        let s = "a;b\n1;2\n3;4\n";
+        let (r, mut rest) = sniff_reader(s.as_bytes(), 6).expect("read error");
+        let mut all = String::new();
+        rest.read_to_string(&mut all).expect("read error");
+
+        assert_eq!(r.dialect.delimiter, ';');
+        assert_eq!(all, s);
+    }
+}
@@ -0,0 +1,196 @@
+use std::fs::File;
+use std::io::{self, Read};
+
+use rusqlite::{Connection, OpenFlags};
+use rusqlite::types::ValueRef;
+
+use crate::table::data::TableData;
+
+/// Extensions of SQLite database files
+pub const SQLITE_EXTENSIONS: [&str; 4] = [".sqlite", ".sqlite3", ".db", ".db3"];
+
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+
+/// Test whether a file is an SQLite database from the header it starts with
+pub fn is_sqlite_file(filename: &str) -> bool {
+    let mut magic = [0; 16];
+
+    File::open(filename)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .map_or(false, |_| magic == SQLITE_MAGIC)
+}
+
+/// List the tables and views of a database by name, leaving out the ones SQLite keeps for itself
+pub fn list_tables(filename: &str) -> io::Result<Vec<String>> {
+    let connection = open_database(filename)?;
+    let mut statement = connection
+        .prepare("SELECT name FROM sqlite_master WHERE type IN ('table', 'view') \
+                  AND name NOT LIKE 'sqlite_%' ORDER BY name")
+        .map_err(sql_error)?;
+    let mut rows = statement.query([]).map_err(sql_error)?;
+    let mut tables = Vec::new();
+
+    while let Some(row) = rows.next().map_err(sql_error)? {
+        tables.push(value_text(row.get_ref(0).map_err(sql_error)?));
+    }
+
+    Ok(tables)
+}
+
+/// A query that selects every row of a table
+pub fn table_query(table: &str) -> String {
+    format!("SELECT * FROM \"{}\"", table.replace('"', "\"\""))
+}
+
+/// Run a query against a database and read the rows it returns
+///
+/// The database is opened read-only so a query can not change it. The names of the
+/// result columns are the header, or the first row if header is false
+pub fn from_query(filename: &str, query: &str, header: bool) -> io::Result<TableData> {
+    from_query_with(filename, query, header, || Ok(()))
+}
+
+/// Run a query like from_query, calling on_row before each row is read
+///
+/// The query stops with the error returned by on_row, which lets it be cancelled
+pub fn from_query_with<F>(filename: &str, query: &str, header: bool, mut on_row: F) -> io::Result<TableData>
+    where F: FnMut() -> io::Result<()>
+{
+    let connection = open_database(filename)?;
+    let mut statement = connection.prepare(query)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    if statement.column_count() == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the query does not return any columns"));
+    }
+
+    let mut names = statement.column_names().iter().map(|c| c.to_string()).collect::<Vec<_>>();
+    let cols = names.len();
+
+    let mut table = TableData::new();
+
+    if header {
+        table.set_header(&mut names);
+    } else {
+        table.set_data(&mut names, cols);
+    }
+
+    let mut rows = statement.query([]).map_err(sql_error)?;
+
+    while let Some(row) = rows.next().map_err(sql_error)? {
+        on_row()?;
+
+        let mut fields = (0..cols)
+            .map(|i| row.get_ref(i).map(value_text))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sql_error)?;
+
+        table.set_data(&mut fields, cols);
+    }
+
+    Ok(table)
+}
+
+/// The text shown for a value. NULL is empty and blobs are shown as SQL literals e.g. X'0A1B'
+fn value_text(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned(),
+        ValueRef::Blob(b) => format!("X'{}'", b.iter().map(|b| format!("{:02X}", b)).collect::<String>()),
+    }
+}
+
+fn open_database(filename: &str) -> io::Result<Connection> {
+    Connection::open_with_flags(filename, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+        .map_err(sql_error)
+}
+
+fn sql_error(e: rusqlite::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    macro_rules! make_strvec {
+        [ $($a:expr),+ ] => {
+            vec![ $($a.to_owned()),+ ]
+        }
+    }
+
+    fn setup_database(target: &str) {
+        let _ = fs::remove_file(target);
+        let connection = Connection::open(target).expect("create failed");
+
+        connection.execute_batch(
+            "CREATE TABLE prices (name TEXT, value REAL, stock INTEGER, image BLOB);
+             INSERT INTO prices VALUES ('value1', 10.5, 3, x'0a1b'), ('value2', NULL, -1, NULL);
+             CREATE VIEW \"in \"\"stock\"\"\" AS SELECT name FROM prices WHERE stock > 0;")
+            .expect("setup failed");
+    }
+
+    #[test]
+    fn test_sqlite_tables() {
+        let f = "sqlite_tables.db";
+        setup_database(f);
+
+        assert_eq!(is_sqlite_file(f), true);
+        assert_eq!(is_sqlite_file("Cargo.toml"), false);
+
+        let tables = list_tables(f).expect("list failed");
+        assert_eq!(tables, make_strvec![ "in \"stock\"", "prices" ]);
+
+        let r = from_query(f, &table_query(&tables[0]), true).expect("query failed");
+        fs::remove_file(f).expect("remove failed");
+
+        assert_eq!(r.header(), &make_strvec![ "name" ]);
+        assert_eq!(r.data(), &make_strvec![ "value1" ]);
+    }
+
+    #[test]
+    fn test_sqlite_query_without_header() {
+        let f = "sqlite_no_header.db";
+        setup_database(f);
+
+        let r = from_query(f, "SELECT name, stock FROM prices ORDER BY stock DESC", false).expect("query failed");
+        fs::remove_file(f).expect("remove failed");
+
+        // the column names are the first row
+        assert_eq!(r.has_headers(), false);
+        assert_eq!(r.data(), &make_strvec![ "name", "stock", "value1", "3", "value2", "-1" ]);
+    }
+
+    #[test]
+    fn test_sqlite_query() {
+        let f = "sqlite_query.db";
+        setup_database(f);
+
+        let r = from_query(f, "SELECT name, value, stock, image AS picture FROM prices ORDER BY stock DESC", true)
+            .expect("query failed");
+
+        assert_eq!(r.header(), &make_strvec![ "name", "value", "stock", "picture" ]);
+        assert_eq!(r.data(), &make_strvec![ "value1", "10.5", "3", "X'0A1B'",
+                                            "value2", "", "-1", "" ]);
+
+        let e = from_query(f, "SELEC name FROM prices", true).err().expect("no error");
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+        // the database is read-only
+        assert!(from_query(f, "DELETE FROM prices", true).is_err());
+        assert_eq!(from_query(f, "SELECT count(*) FROM prices", true).expect("query failed").data(), &make_strvec![ "2" ]);
+
+        let mut rows = 0;
+        let e = from_query_with(f, "SELECT * FROM prices", true, || {
+            rows += 1;
+            if rows > 1 { Err(io::Error::new(io::ErrorKind::Other, "stop")) } else { Ok(()) }
+        });
+        fs::remove_file(f).expect("remove failed");
+
+        assert_eq!(e.err().map(|e| e.to_string()), Some("stop".to_owned()));
+    }
+}
@@ -0,0 +1,218 @@
+use std::io;
+
+use calamine::{open_workbook_auto, DataType, Range, Reader};
+
+use crate::table::data::{RecordSource, TableData};
+
+/// Extensions of the spreadsheet workbooks that can be read
+pub const WORKBOOK_EXTENSIONS: [&str; 5] = [".xlsx", ".xlsm", ".xlsb", ".xls", ".ods"];
+
+/// Days from the start of the 1900 date system of spreadsheets, 30 December 1899, to 1 January 1970
+const EPOCH_1970: i64 = 25_569;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Test whether a file is a spreadsheet workbook from its extension
+pub fn is_workbook_file(filename: &str) -> bool {
+    let filename = filename.to_lowercase();
+    WORKBOOK_EXTENSIONS.iter().any(|e| filename.ends_with(e))
+}
+
+/// List the names of the sheets of a workbook in the order they appear in it
+pub fn list_sheets(filename: &str) -> io::Result<Vec<String>> {
+    let workbook = open_workbook_auto(filename).map_err(invalid_data)?;
+    Ok(workbook.sheet_names().to_vec())
+}
+
+/// Read a sheet of a workbook, or its first sheet if None
+///
+/// Returns the name of the sheet that was read alongside the table. Cells hold the values
+/// a spreadsheet shows, which for formulas are the values cached when the workbook was
+/// last saved
+pub fn from_sheet(filename: &str, sheet: Option<&str>, header: bool) -> io::Result<(TableData, String)> {
+    let mut workbook = open_workbook_auto(filename).map_err(invalid_data)?;
+
+    let name = match sheet {
+        Some(sheet) => sheet.to_string(),
+        None => workbook.sheet_names().first().cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{} has no sheets", filename)))?
+    };
+
+    let range = workbook.worksheet_range(&name)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} has no sheet named {}", filename, name)))?
+        .map_err(invalid_data)?;
+
+    Ok((from_range(&range, header), name))
+}
+
+/// Build a table from the used cells of a sheet
+///
+/// Rows without any values are left out. The source of a row is its 1-based row number in
+/// the sheet, as workbooks have no meaningful byte offsets
+pub fn from_range(range: &Range<DataType>, header: bool) -> TableData {
+    let mut table = TableData::new();
+    let (_, cols) = range.get_size();
+    let first_row = range.start().map_or(0, |(row, _)| row as usize);
+
+    for (i, cells) in range.rows().enumerate() {
+        if cells.iter().all(|c| *c == DataType::Empty) {
+            continue;
+        }
+
+        let mut fields = cells.iter().map(cell_text).collect::<Vec<_>>();
+
+        if header && !table.has_headers() {
+            table.set_header(&mut fields);
+        } else {
+            table.set_data(&mut fields, cols);
+            table.sources.push(RecordSource { line: first_row + i + 1, offset: 0 });
+        }
+    }
+
+    table
+}
+
+/// The text a spreadsheet shows for a cell with its default formatting
+///
+/// Whole numbers have no decimal point, dates and times are written as in ISO 8601 and
+/// errors keep their spreadsheet code such as "#DIV/0!"
+pub fn cell_text(cell: &DataType) -> String {
+    match cell {
+        DataType::Empty => String::new(),
+        DataType::String(s) | DataType::DateTimeIso(s) | DataType::DurationIso(s) => s.clone(),
+        DataType::Int(i) => i.to_string(),
+        DataType::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => format!("{:.0}", f),
+        DataType::Float(f) => f.to_string(),
+        DataType::Bool(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
+        DataType::DateTime(serial) => date_time_text(*serial),
+        DataType::Duration(days) => duration_text(*days),
+        DataType::Error(e) => e.to_string(),
+    }
+}
+
+/// Write a serial date of the 1900 date system as "YYYY-MM-DD", "YYYY-MM-DD HH:MM:SS" or
+/// "HH:MM:SS" for a time without a date
+fn date_time_text(serial: f64) -> String {
+    let (mut days, seconds) = split_days(serial);
+
+    if days == 0 {
+        return time_text(seconds);
+    }
+
+    // spreadsheets count the 29 February 1900 that never was, so earlier dates are a day off
+    if days < 60 {
+        days += 1;
+    }
+
+    let (y, m, d) = civil_from_days(days - EPOCH_1970);
+
+    if seconds == 0 {
+        format!("{:04}-{:02}-{:02}", y, m, d)
+    } else {
+        format!("{:04}-{:02}-{:02} {}", y, m, d, time_text(seconds))
+    }
+}
+
+/// Write a duration in days as hours, minutes and seconds e.g. "36:00:00"
+fn duration_text(days: f64) -> String {
+    let seconds = (days * SECONDS_PER_DAY).round() as i64;
+    let sign = if seconds < 0 { "-" } else { "" };
+    let seconds = seconds.abs();
+
+    format!("{}{}:{:02}:{:02}", sign, seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+fn time_text(seconds: i64) -> String {
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Split a serial date into whole days and the seconds of the time of day, rounded to the second
+fn split_days(serial: f64) -> (i64, i64) {
+    let days = serial.floor();
+    let seconds = ((serial - days) * SECONDS_PER_DAY).round() as i64;
+
+    if seconds >= SECONDS_PER_DAY as i64 {
+        (days as i64 + 1, 0)
+    } else {
+        (days as i64, seconds)
+    }
+}
+
+/// Year, month and day of a number of days since 1 January 1970 in the proleptic Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+
+    (y, m, d)
+}
+
+fn invalid_data(e: calamine::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use calamine::CellErrorType;
+
+    use super::*;
+
+    macro_rules! make_strvec {
+        [ $($a:expr),+ ] => {
+            vec![ $($a.to_owned()),+ ]
+        }
+    }
+
+    #[test]
+    fn test_cell_text() {
+        assert_eq!(cell_text(&DataType::Float(12.0)), "12");
+        assert_eq!(cell_text(&DataType::Float(12.5)), "12.5");
+        assert_eq!(cell_text(&DataType::Int(-3)), "-3");
+        assert_eq!(cell_text(&DataType::Bool(true)), "TRUE");
+        assert_eq!(cell_text(&DataType::Error(CellErrorType::Div0)), "#DIV/0!");
+        assert_eq!(cell_text(&DataType::DateTimeIso("2023-05-01".to_string())), "2023-05-01");
+        assert_eq!(cell_text(&DataType::Empty), "");
+    }
+
+    #[test]
+    fn test_date_time_text() {
+        assert_eq!(cell_text(&DataType::DateTime(1.0)), "1900-01-01");
+        assert_eq!(cell_text(&DataType::DateTime(59.0)), "1900-02-28");
+        assert_eq!(cell_text(&DataType::DateTime(61.0)), "1900-03-01");
+        assert_eq!(cell_text(&DataType::DateTime(45_000.0)), "2023-03-15");
+        assert_eq!(cell_text(&DataType::DateTime(45_000.75)), "2023-03-15 18:00:00");
+        assert_eq!(cell_text(&DataType::DateTime(0.5)), "12:00:00");
+        assert_eq!(cell_text(&DataType::Duration(1.5)), "36:00:00");
+    }
+
+    #[test]
+    fn test_from_range() {
+        let mut range = Range::new((1, 1), (4, 2));
+        range.set_value((1, 1), DataType::String("Name".to_string()));
+        range.set_value((1, 2), DataType::String("Value".to_string()));
+        range.set_value((2, 1), DataType::String("value1".to_string()));
+        range.set_value((2, 2), DataType::Float(10.0));
+        range.set_value((4, 2), DataType::Bool(false));
+
+        let r = from_range(&range, true);
+
+        assert_eq!(r.header(), &make_strvec![ "Name", "Value" ]);
+        assert_eq!(r.data(), &make_strvec![ "value1", "10", "", "FALSE" ]);
+        assert_eq!(r.source(1), Some(RecordSource { line: 5, offset: 0 }));
+
+        assert_eq!(from_range(&range, false).rows(), 3);
+    }
+
+    #[test]
+    fn test_is_workbook_file() {
+        assert!(is_workbook_file("Report.XLSX"));
+        assert!(is_workbook_file("budget.ods"));
+        assert!(!is_workbook_file("data.csv"));
+    }
+}
@@ -4,7 +4,7 @@ use std::sync::{Arc, Mutex};
 
 use crate::{BoxedResult, resource};
 use crate::table;
-use crate::table::TableData;
+use crate::table::{Dialect, Encoding, LoadRequest, Loader};
 use crate::ui::{Component, OpenFileInfo};
 use crate::ui::AppState;
 use crate::ui::menu::MenuBuilder;
@@ -28,6 +28,20 @@ pub struct MainWindow {
     pub find_dialog: nwg::GridLayout,
     /// About application dialog
     pub about_dialog: nwg::GridLayout,
+    /// Wakes up the UI thread when a background load reports progress or finishes
+    pub load_notice: nwg::Notice,
+    /// File being loaded on a worker thread
+    pub loader: RefCell<Option<Loader>>,
+    /// Dialog for picking a table of a database or typing a query
+    pub query_window: nwg::Window,
+    /// Tables and views of the database being queried
+    pub query_tables: nwg::ListBox<String>,
+    /// Query to run
+    pub query_input: nwg::TextBox,
+    /// Runs the query
+    pub query_run: nwg::Button,
+    /// Database the query dialog is open for
+    pub query_file: RefCell<Option<String>>,
 }
 
 impl MainWindow {
@@ -41,6 +55,11 @@ impl MainWindow {
 
     /// Perform pre-destruction tasks for the main window and disable events
     pub fn exit(&self, state: &mut AppState) {
+        // Stop any background load
+        if let Some(loader) = self.loader.borrow_mut().take() {
+            loader.cancel();
+        }
+
         // Store the window position and size
         state.set_window_pos(self.window.position());
         state.set_window_size(self.window.size());
@@ -66,27 +85,263 @@ impl MainWindow {
         let selected = self.open_file_picker_dialog(&self.file_dialog);
 
         match selected {
+            // the dialog was cancelled so the open file and any load in progress are kept
+            Ok(s) if s.is_empty() => Ok(()),
             Ok(s) => {
                 eprintln!("Selected file: {}", s);
-
-                if let Some(ofi) = self.read_file(&s) {
-                    state.load_data(ofi);
-                    // TODO: Layout the data
-                }
-
-                Ok(())
+                self.cmd_open_path(state, &s, false)
             },
             Err(e) => Err(e)
         }
     }
 
+    /// Open a file named on the command line or picked in the file dialog
+    ///
+    /// "-" reads stdin, which like a named pipe can not be read again, so lenient is the
+    /// only way to load one with malformed rows. A file with malformed rows can be loaded
+    /// again leniently when a strict load fails
+    pub fn cmd_open_path(&self, state: &mut AppState, filename: &str, lenient: bool) -> BoxedResult<()> {
+        if table::is_stream(filename) {
+            self.start_loading(filename, Dialect { lenient, .. Dialect::default() }, None, None, None);
+            return Ok(());
+        }
+
+        // A ZIP archive opens one of its members
+        let s = match self.choose_member(filename)? {
+            Some(s) => s,
+            None => return Ok(())
+        };
+
+        // A database runs the query it was last opened with or asks for one
+        if table::is_sqlite_file(&s) {
+            return match state.recent_query(&s) {
+                Some(query) => {
+                    self.start_loading(&s, MainWindow::query_dialect(state, &s), None, None, Some(query));
+                    Ok(())
+                },
+                None => self.show_query_dialog(&s, None)
+            };
+        }
+
+        // A workbook opens one of its sheets and an HTML or Markdown file one of its tables
+        let sheet = if table::is_workbook_file(&s) || table::is_markup_file(&s) {
+            match self.choose_sheet(&s)? {
+                Some(sheet) => Some(sheet),
+                None => return Ok(())
+            }
+        } else {
+            None
+        };
+
+        let mut dialect = self.detect_dialect(&s);
+        dialect.lenient = lenient;
+
+        // A header chosen by the user takes precedence over header detection
+        if let Some(header) = state.header_override(&s) {
+            dialect.header = header;
+        }
+
+        self.start_loading(&s, dialect, None, sheet, None);
+
+        Ok(())
+    }
+
     /// Close an open file and remove the layout
     pub fn cmd_close_file(&self, state: &mut AppState) -> BoxedResult<()> {
+        self.cmd_cancel_load()?;
         let _ofi = state.unload_data();
         eprintln!("cmd_close_file: Closing open file");
         Ok(())
     }
 
+    /// Toggle whether the first row of the open file is a header and reload it
+    /// The choice is remembered with the recent file entry
+    pub fn cmd_toggle_header(&self, state: &mut AppState) -> BoxedResult<()> {
+        if state.file_data().map_or(false, |ofi| ofi.is_stdin()) {
+            nwg::modal_info_message(&self.window, "Toggle Header", "Data read from stdin can not be read again");
+            return Ok(());
+        }
+
+        let reload = state.file_data()
+            .map(|ofi| (ofi.name.clone(),
+                        Dialect { header: !ofi.dialect.header, .. ofi.dialect.clone() },
+                        ofi.encoding.encoding,
+                        ofi.sheet.clone(),
+                        ofi.query.clone()));
+
+        if let Some((name, dialect, encoding, sheet, query)) = reload {
+            eprintln!("cmd_toggle_header: header {}", dialect.header);
+            state.set_header_override(&name, Some(dialect.header));
+
+            self.start_loading(&name, dialect, Some(encoding), sheet, query);
+        }
+
+        Ok(())
+    }
+
+    /// Show the lines skipped at the start of the open file
+    pub fn cmd_view_preamble(&self, state: &mut AppState) -> BoxedResult<()> {
+        const MAX_LISTED: usize = 40;
+
+        let preamble = match state.file_data() {
+            Some(ofi) if !ofi.preamble.is_empty() => &ofi.preamble,
+            Some(_) => {
+                nwg::modal_info_message(&self.window, "View Preamble", "No lines were skipped at the start of the file");
+                return Ok(());
+            },
+            None => return Ok(())
+        };
+
+        let mut content = preamble.iter()
+            .take(MAX_LISTED)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if preamble.len() > MAX_LISTED {
+            content.push_str(&format!("\n... and {} more lines", preamble.len() - MAX_LISTED));
+        }
+
+        nwg::modal_info_message(&self.window, "View Preamble", &content);
+        Ok(())
+    }
+
+    /// Run another query against the open database
+    pub fn cmd_query(&self, state: &mut AppState) -> BoxedResult<()> {
+        match state.file_data().filter(|ofi| ofi.query.is_some()) {
+            Some(ofi) => self.show_query_dialog(&ofi.name, ofi.query.clone()),
+            None => {
+                nwg::modal_info_message(&self.window, resource::CDialogQuery::TITLE,
+                                        "Open an SQLite database to query it");
+                Ok(())
+            }
+        }
+    }
+
+    /// Fill in the query for the table picked in the query dialog
+    pub fn cmd_pick_table(&self) -> BoxedResult<()> {
+        if let Some(name) = self.query_tables.selection_string() {
+            self.query_input.set_text(&table::table_query(&name));
+        }
+
+        Ok(())
+    }
+
+    /// Run the query typed in the query dialog
+    /// The query is remembered with the recent file entry once it has run
+    pub fn cmd_run_query(&self, state: &mut AppState) -> BoxedResult<()> {
+        let query = self.query_input.text();
+        let filename = self.query_file.borrow().clone();
+
+        if let Some(filename) = filename.filter(|_| !query.trim().is_empty()) {
+            eprintln!("cmd_run_query: {}", query);
+            self.query_window.set_visible(false);
+            self.start_loading(&filename, MainWindow::query_dialect(state, &filename), None, None, Some(query));
+        }
+
+        Ok(())
+    }
+
+    /// Stop loading a file in the background
+    /// The file that was open before stays open
+    pub fn cmd_cancel_load(&self) -> BoxedResult<()> {
+        if let Some(loader) = self.loader.borrow().as_ref() {
+            eprintln!("cmd_cancel_load: cancelling {}", loader.request().filename);
+            loader.cancel();
+        }
+
+        Ok(())
+    }
+
+    /// Show the progress of a background load and take its result once it has finished
+    ///
+    /// Called whenever the loader wakes up the UI thread
+    /// If the file is malformed the user can choose to load it leniently instead
+    pub fn cmd_load_progress(&self, state: &mut AppState) -> BoxedResult<()> {
+        let finished = match self.loader.borrow().as_ref() {
+            Some(loader) => {
+                if let Some(p) = loader.progress() {
+                    let name = OpenFileInfo::name_of(&loader.request().filename);
+
+                    // the size of a stream is not known
+                    if p.total == 0 {
+                        self.status_bar.set_text(0, &format!("Loading {}... ({} rows)", name, p.rows));
+                    } else {
+                        self.status_bar.set_text(0, &format!("Loading {}... {}% ({} rows)", name, p.percent(), p.rows));
+                    }
+                }
+
+                loader.is_finished()
+            },
+            None => false
+        };
+
+        if !finished {
+            return Ok(());
+        }
+
+        let loader = self.loader.borrow_mut().take().unwrap();
+        let request = loader.request().clone();
+
+        let loaded = match loader.wait() {
+            Ok(Ok(loaded)) => loaded,
+            Ok(Err(e)) if !request.dialect.lenient && table::is_stream(&request.filename) => {
+                self.status_bar.set_text(0, "");
+                nwg::modal_error_message(&self.window, "Open File",
+                                         &format!("{}\n\n{} can not be read again. Open it with --lenient to load \
+                                                   malformed rows anyway.", e, OpenFileInfo::name_of(&request.filename)));
+                return Ok(());
+            },
+            Ok(Err(e)) if !request.dialect.lenient => {
+                self.status_bar.set_text(0, "");
+
+                if self.confirm_lenient(&e) {
+                    let lenient = Dialect { lenient: true, .. request.dialect };
+                    self.start_loading(&request.filename, lenient, request.encoding, request.sheet, request.query);
+                }
+
+                return Ok(());
+            },
+            Err(e) if table::is_cancelled(&e) => {
+                self.status_bar.set_text(0, &format!("Loading {} cancelled", OpenFileInfo::name_of(&request.filename)));
+                return Ok(());
+            },
+            Ok(Err(e)) => {
+                self.status_bar.set_text(0, "");
+                return Err(Box::new(e));
+            },
+            Err(e) => {
+                self.status_bar.set_text(0, "");
+                return Err(Box::new(e));
+            }
+        };
+
+        let filename = OpenFileInfo::name_of(&request.filename);
+        let name = match &loaded.sheet {
+            Some(sheet) => format!("{} [{}]", filename, sheet),
+            None => filename.clone()
+        };
+        let mapped = if loaded.mapped { ", mapped" } else { "" };
+        let errors = loaded.data.errors();
+
+        if errors.is_empty() {
+            self.status_bar.set_text(0, &format!("{} ({}{})", name, loaded.encoding, mapped));
+        } else {
+            self.status_bar.set_text(0, &format!("{} ({}{}) - {} problems",
+                                                 name, loaded.encoding, mapped, errors.len()));
+            self.show_errors(&errors);
+        }
+
+        state.load_data(OpenFileInfo {
+            name: filename, sheet: loaded.sheet, query: request.query,
+            dialect: loaded.dialect.unwrap_or(request.dialect), encoding: loaded.encoding,
+            preamble: loaded.data.preamble().to_vec(), data: loaded.data
+        });
+        // TODO: Layout the data
+
+        Ok(())
+    }
+
     /// Exit the application
     /// Uses Window::close() to send an exit message to the main window
     pub fn cmd_exit(&self) -> BoxedResult<()> {
@@ -130,35 +385,207 @@ impl MainWindow {
         Ok(String::new())
     }
 
-    /// Read a selected file into a TableData structure
+    /// Pick the member of a ZIP archive to open
+    ///
+    /// Returns the path to the member such as "bundle.zip!/data.csv" or None if the archive
+    /// has no delimited members or none was chosen. Any other file is returned as it is,
+    /// including workbooks that are ZIP archives themselves
+    fn choose_member(&self, filename: &str) -> BoxedResult<Option<String>> {
+        if !table::is_zip_file(filename) || table::is_workbook_file(filename) {
+            return Ok(Some(filename.to_string()));
+        }
+
+        let members = table::list_members(filename)?;
+
+        match members.len() {
+            0 => {
+                nwg::modal_info_message(&self.window, "Open File",
+                                        &format!("{} has no CSV, TSV, text or JSON files", filename));
+                return Ok(None);
+            },
+            1 => return Ok(Some(table::member_path(filename, &members[0]))),
+            _ => {}
+        }
+
+        // Offer each member in turn until one is chosen
+        for (i, member) in members.iter().enumerate() {
+            let params = nwg::MessageParams {
+                title: "Open File",
+                content: &format!("Open {} from {}? ({} of {})\n\nChoose No to see the next file.",
+                                  member, filename, i + 1, members.len()),
+                buttons: nwg::MessageButtons::YesNoCancel,
+                icons: nwg::MessageIcons::Question
+            };
+
+            match nwg::modal_message(&self.window, &params) {
+                nwg::MessageChoice::Yes => return Ok(Some(table::member_path(filename, member))),
+                nwg::MessageChoice::No => continue,
+                _ => break
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Pick the sheet of a workbook, or the table of an HTML or Markdown file, to open
+    ///
+    /// Returns the name of the sheet or None if the file has no sheets or none was chosen
+    fn choose_sheet(&self, filename: &str) -> BoxedResult<Option<String>> {
+        let (sheets, kind) = if table::is_workbook_file(filename) {
+            (table::list_sheets(filename)?, "sheet")
+        } else {
+            (table::list_markup_tables(filename)?, "table")
+        };
+
+        match sheets.len() {
+            0 => {
+                nwg::modal_info_message(&self.window, "Open File", &format!("{} has no {}s", filename, kind));
+                return Ok(None);
+            },
+            1 => return Ok(Some(sheets[0].clone())),
+            _ => {}
+        }
+
+        // Offer each sheet in turn until one is chosen
+        for (i, sheet) in sheets.iter().enumerate() {
+            let params = nwg::MessageParams {
+                title: "Open File",
+                content: &format!("Open {} {} of {}? ({} of {})\n\nChoose No to see the next {}.",
+                                  kind, sheet, filename, i + 1, sheets.len(), kind),
+                buttons: nwg::MessageButtons::YesNoCancel,
+                icons: nwg::MessageIcons::Question
+            };
+
+            match nwg::modal_message(&self.window, &params) {
+                nwg::MessageChoice::Yes => return Ok(Some(sheet.clone())),
+                nwg::MessageChoice::No => continue,
+                _ => break
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Show the dialog for querying a database
     ///
-    /// If the extension ends with:
-    ///     .csv:      comma separated value format
-    ///     .tsv|.txt: tab separated value format
+    /// The query starts as the given one or else selects every row of the first table
+    fn show_query_dialog(&self, filename: &str, query: Option<String>) -> BoxedResult<()> {
+        let tables = table::list_tables(filename)?;
+        let query = query.or_else(|| tables.first().map(|t| table::table_query(t))).unwrap_or_default();
+
+        self.query_tables.set_collection(tables);
+        self.query_input.set_text(&query);
+        *self.query_file.borrow_mut() = Some(filename.to_string());
+
+        self.query_window.set_text(&format!("{} - {}", resource::CDialogQuery::TITLE, filename));
+        self.query_window.set_visible(true);
+        self.query_input.set_focus();
+
+        Ok(())
+    }
+
+    /// The dialect of a database, whose column names are the header unless the user
+    /// toggled it off for the file
+    fn query_dialect(state: &AppState, filename: &str) -> Dialect {
+        Dialect { header: state.header_override(filename).unwrap_or(true), .. Dialect::default() }
+    }
+
+    /// Sniff the dialect of a file from its contents
     ///
-    /// Returns an OpenFileInfo structure with the data and filename if successful
-    fn read_file(&self, filename: &str) -> Option<OpenFileInfo> {
-        let msg= move |content| nwg::fatal_message("Open File", format!("{}", content).as_str());
-        let mut data: Option<TableData> = None;
+    /// Falls back to the file extension when the sniffer has no confidence in its guess
+    /// Lines that look like a preamble before the table are only skipped if the user agrees
+    /// Workbooks and columnar files are not sniffed and their first row or column names are
+    /// taken to be a header. Neither are HTML and Markdown files, whose tables mark their header
+    fn detect_dialect(&self, filename: &str) -> Dialect {
+        if table::is_workbook_file(filename) || table::is_markup_file(filename)
+            || table::detect_columnar_file(filename).is_some() {
+            return Dialect { header: true, .. Dialect::default() };
+        }
 
-        // Map error types into formatted strings to simplify display logic
-        if filename.ends_with("csv") {
-            data = table::from_csv_file(filename, false)
-                .map_err(|e| e.to_string()).unwrap()
-                .map_or_else(|e| { msg(e); None }, |v| Some(v));
+        match table::sniff_file(filename) {
+            Ok(sniffed) if sniffed.confidence > 0.0 => {
+                eprintln!("Sniffed dialect: {:?}, confidence: {}", sniffed.dialect, sniffed.confidence);
 
+                match sniffed.skip_preamble {
+                    Some(skipping) if self.confirm_preamble(filename, skipping.skip_lines) => skipping,
+                    _ => sniffed.dialect
+                }
+            },
+            _ => Dialect::from_extension(filename).unwrap_or_default()
         }
-        else if filename.ends_with("tsv") || filename.ends_with("txt") {
-            data = table::from_tsv_file(filename, false)
-                .map_err(|e| e.to_string()).unwrap()
-                .map_or_else(|e| { msg(e); None }, |v| Some(v));
+    }
+
+    /// Start loading a file on a worker thread, cancelling any load in progress
+    ///
+    /// The dialect describes the delimiter, quoting and header of the file
+    /// The encoding is detected from the contents of the file if None
+    /// The sheet is read from a workbook, or its first sheet if None
+    /// The query is run against a database
+    /// Large files are memory mapped, see CDialogOpenFile::MAP_SIZE
+    fn start_loading(&self, filename: &str, dialect: Dialect, encoding: Option<Encoding>,
+                     sheet: Option<String>, query: Option<String>) {
+        if let Some(previous) = self.loader.borrow_mut().take() {
+            previous.cancel();
         }
 
-        if let Some(d) = data {
-            return Some(OpenFileInfo { name: filename.to_string(), data: d });
+        let request = LoadRequest {
+            filename: filename.to_string(),
+            dialect,
+            encoding,
+            map_size: Some(resource::CDialogOpenFile::MAP_SIZE),
+            sheet,
+            query,
+        };
+
+        let notice = self.load_notice.sender();
+
+        self.status_bar.set_text(0, &format!("Loading {}...", filename));
+        *self.loader.borrow_mut() = Some(Loader::spawn(request, move || notice.notice()));
+    }
+
+    /// Ask the user whether a malformed file should be loaded anyway
+    fn confirm_lenient(&self, error: &table::TableDataValidationError) -> bool {
+        let params = nwg::MessageParams {
+            title: "Open File",
+            content: &format!("{}\n\nLoad the file anyway? Short rows are padded, long rows get \
+                               extra columns and stray quotes are kept as text.", error),
+            buttons: nwg::MessageButtons::YesNo,
+            icons: nwg::MessageIcons::Question
+        };
+
+        nwg::modal_message(&self.window, &params) == nwg::MessageChoice::Yes
+    }
+
+    /// Ask the user whether the lines that seem to come before the table of a file are skipped
+    fn confirm_preamble(&self, filename: &str, lines: usize) -> bool {
+        let described = if lines == 1 { "The first line seems".to_owned() } else { format!("The first {} lines seem", lines) };
+        let params = nwg::MessageParams {
+            title: "Open File",
+            content: &format!("{} to come before the table in {}.\n\n\
+                               Skip them? Skipped lines are listed by Edit > View preamble.",
+                              described, OpenFileInfo::name_of(filename)),
+            buttons: nwg::MessageButtons::YesNo,
+            icons: nwg::MessageIcons::Question
+        };
+
+        nwg::modal_message(&self.window, &params) == nwg::MessageChoice::Yes
+    }
+
+    /// List the problems found while loading a file leniently
+    fn show_errors(&self, errors: &[table::TableDataValidationError]) {
+        const MAX_LISTED: usize = 20;
+
+        let mut content = errors.iter()
+            .take(MAX_LISTED)
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if errors.len() > MAX_LISTED {
+            content.push_str(&format!("\n... and {} more", errors.len() - MAX_LISTED));
         }
 
-        None
+        nwg::modal_info_message(&self.window, "Open File", &content);
     }
 
     /// Search the menu tree and locate a menu container by its handle
@@ -211,6 +638,53 @@ impl MainWindow {
             .build(&mut data.borrow_mut().status_bar)
     }
 
+    /// Create the notice a background load uses to wake up the UI thread
+    /// The parent is the main window
+    pub fn create_load_notice(data: Rc<RefCell<MainWindow>>) -> NwgResult<()> {
+        let parent = data.borrow().window.handle.clone();
+
+        nwg::Notice::builder()
+            .parent(&parent)
+            .build(&mut data.borrow_mut().load_notice)
+    }
+
+    /// Create the hidden dialog for querying a database
+    /// The dialog is a window of its own owned by the main window
+    pub fn create_query_dialog(data: Rc<RefCell<MainWindow>>) -> NwgResult<()> {
+        use crate::resource::CDialogQuery;
+
+        let parent = data.borrow().window.handle.clone();
+        let (width, height) = CDialogQuery::WINDOW_SIZE;
+
+        nwg::Window::builder()
+            .flags(nwg::WindowFlags::WINDOW)
+            .size(CDialogQuery::WINDOW_SIZE)
+            .title(CDialogQuery::TITLE)
+            .parent(Some(parent))
+            .build(&mut data.borrow_mut().query_window)?;
+
+        let window = data.borrow().query_window.handle.clone();
+
+        nwg::ListBox::builder()
+            .position((10, 10))
+            .size((150, height - 20))
+            .parent(&window)
+            .build(&mut data.borrow_mut().query_tables)?;
+
+        nwg::TextBox::builder()
+            .position((170, 10))
+            .size((width - 180, height - 60))
+            .parent(&window)
+            .build(&mut data.borrow_mut().query_input)?;
+
+        nwg::Button::builder()
+            .text(CDialogQuery::RUN)
+            .position((width - 110, height - 40))
+            .size((100, 30))
+            .parent(&window)
+            .build(&mut data.borrow_mut().query_run)
+    }
+
     /// Create the dialog for opening files
     pub fn create_file_picker_dialog(data: Rc<RefCell<MainWindow>>) -> NwgResult<()> {
         nwg::FileDialog::builder()
@@ -224,11 +698,13 @@ impl MainWindow {
     ///
     /// Creates a menu bar with the following menus:
     ///
-    ///     File            | Edit              | Help
-    ///       Open File     |   Find            |   About
-    ///       Close File    |   ----            |
-    ///       ----------    |   Preferences     |
-    ///       Exit          |                   |
+    ///     File             | Edit                  | Help
+    ///       Open File      |   Find                |   About
+    ///       Close File     |   First Row Is Header |
+    ///       Query Database |   View Preamble       |
+    ///       Cancel Loading |   ----                |
+    ///       ----------     |   Preferences         |
+    ///       Exit           |                       |
     ///
     pub fn create_menus(data: Rc<RefCell<MainWindow>>) -> NwgResult<()> {
         use crate::ui::menu::MenuBuildType as BT;
@@ -238,7 +714,7 @@ impl MainWindow {
 
         // File menu template
         //
-        // File { Open File, Close File, -- Separator --, Exit }
+        // File { Open File, Close File, Query Database, Cancel Loading, -- Separator --, Exit }
         let file_template = BT::Menu(
             CMenuFile::NAME.to_string(),
             false,
@@ -251,6 +727,13 @@ impl MainWindow {
                              Box::new(move |a,s,_e,_d|
                                  MainWindow::cmd_close_file(a, s))
                 ),
+                BT::MenuItem(CMenuFile::CHILD[4].to_string(), false,
+                             Box::new(move |a,s,_e,_d|
+                                 MainWindow::cmd_query(a, s))
+                ),
+                BT::MenuItem(CMenuFile::CHILD[3].to_string(), false,
+                             Box::new(move |a,_s,_e,_d| MainWindow::cmd_cancel_load(a))
+                ),
                 BT::MenuSeparator,
                 BT::MenuItem(CMenuFile::CHILD[2].to_string(), false,
                              Box::new(move |a,_s,_e,_d| MainWindow::cmd_exit(a))
@@ -260,7 +743,7 @@ impl MainWindow {
 
         // Edit menu template
         //
-        // Edit { Find, -- Separator --, Preferences }
+        // Edit { Find, First Row Is Header, View Preamble, -- Separator --, Preferences }
         let edit_template = BT::Menu(
             CMenuEdit::NAME.to_string(),
             false,
@@ -269,6 +752,14 @@ impl MainWindow {
                              Box::new(move |a,_s,_e,d|
                                  MainWindow::cmd_find(a, d))
                 ),
+                BT::MenuItem(CMenuEdit::CHILD[2].to_string(), false,
+                             Box::new(move |a,s,_e,_d|
+                                 MainWindow::cmd_toggle_header(a, s))
+                ),
+                BT::MenuItem(CMenuEdit::CHILD[3].to_string(), false,
+                             Box::new(move |a,s,_e,_d|
+                                 MainWindow::cmd_view_preamble(a, s))
+                ),
                 BT::MenuSeparator,
                 BT::MenuItem(CMenuEdit::CHILD[1].to_string(), false,
                              Box::new(move |a,s,_e,d|
@@ -39,6 +39,10 @@ impl NativeUiEx<MainWindowUi, Arc<Mutex<AppState>>> for MainWindow {
         MainWindow::create_status_bar(Rc::clone(&ui.inner))?;
         // Create the file picker dialog
         MainWindow::create_file_picker_dialog(Rc::clone(&ui.inner))?;
+        // Create the notice for background loads
+        MainWindow::create_load_notice(Rc::clone(&ui.inner))?;
+        // Create the hidden dialog for querying databases
+        MainWindow::create_query_dialog(Rc::clone(&ui.inner))?;
 
         // Weak references for the event handler closure
         let evt_ui = Rc::downgrade(&ui.inner);
@@ -55,6 +59,29 @@ impl NativeUiEx<MainWindowUi, Arc<Mutex<AppState>>> for MainWindow {
                         E::OnWindowClose => {
                             if &handle == &ui.borrow().window.handle {
                                 MainWindow::exit(&ui.borrow(), &mut state.lock().unwrap());
+                            } else if &handle == &ui.borrow().query_window.handle {
+                                // The query dialog is hidden rather than destroyed so it can be shown again
+                                if let nwg::EventData::OnWindowClose(data) = &evt_data {
+                                    data.close(false);
+                                }
+
+                                ui.borrow().query_window.set_visible(false);
+                            }
+                        },
+                        // A table picked in the query dialog
+                        E::OnListBoxSelect => {
+                            if &handle == &ui.borrow().query_tables.handle {
+                                if let Err(e) = MainWindow::cmd_pick_table(&ui.borrow()) {
+                                    nwg::error_message("Query", &e.to_string());
+                                }
+                            }
+                        },
+                        // The run button of the query dialog
+                        E::OnButtonClick => {
+                            if &handle == &ui.borrow().query_run.handle {
+                                if let Err(e) = MainWindow::cmd_run_query(&ui.borrow(), &mut state.lock().unwrap()) {
+                                    nwg::error_message("Query", &e.to_string());
+                                }
                             }
                         },
                         // WM_COMMAND message generated by nwg::MenuItem
@@ -68,6 +95,22 @@ impl NativeUiEx<MainWindowUi, Arc<Mutex<AppState>>> for MainWindow {
                                 // Execute the command
                                 menu.run(params)
                                     .map_err(|e| nwg::error_message(menu.name(), &format!("{:?}", e)));
+
+                                // A load that finished while the command held the state is taken now
+                                if let Err(e) = MainWindow::cmd_load_progress(&ui.borrow(), &mut state.lock().unwrap()) {
+                                    nwg::error_message("Open File", &e.to_string());
+                                }
+                            }
+                        },
+                        // Sent from the worker thread of a background load
+                        E::OnNotice => {
+                            if &handle == &ui.borrow().load_notice.handle {
+                                // A command showing a modal dialog can still hold the state
+                                if let Ok(mut state) = state.try_lock() {
+                                    if let Err(e) = MainWindow::cmd_load_progress(&ui.borrow(), &mut state) {
+                                        nwg::error_message("Open File", &e.to_string());
+                                    }
+                                }
                             }
                         }
                         _ => {}
@@ -76,6 +119,10 @@ impl NativeUiEx<MainWindowUi, Arc<Mutex<AppState>>> for MainWindow {
             }
         };
 
+        // The query dialog is not a child of the main window so it is bound separately
+        ui.control_handlers.borrow_mut()
+            .push(nwg::full_bind_event_handler(&ui.query_window.handle, handle_events.clone()));
+
         // Bind the default event handler
         *ui.default_handler.borrow_mut() =
             Some(nwg::full_bind_event_handler(&ui.window.handle, handle_events));
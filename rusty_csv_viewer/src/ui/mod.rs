@@ -1,8 +1,8 @@
 pub use app::MainWindow;
 pub use app_ui::MainWindowUi;
 pub use component::{Component, ComponentParams};
-pub use state::app_state::{AppState, OpenFileInfo};
-pub use state::settings::Settings;
+pub use state::app_state::{AppState, OpenFileInfo, STDIN_NAME};
+pub use state::settings::{RecentFile, Settings};
 
 mod app;
 mod app_ui;
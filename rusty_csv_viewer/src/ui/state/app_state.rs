@@ -1,13 +1,40 @@
 use crate::BoxedResult;
-use crate::table::TableData;
+use crate::table::{self, DetectedEncoding, Dialect, Table};
 use crate::utils::Point;
 
-use super::settings::Settings;
+use super::settings::{RecentFile, Settings};
 
-/// Stores the name of the file and its tabulated contents
+/// Name of the open file when its data was read from stdin
+pub const STDIN_NAME: &str = "<stdin>";
+
+/// Stores the name of the file, how it was read and its tabulated contents
 pub struct OpenFileInfo {
+    /// Path of the file, which is the workbook for a sheet of a spreadsheet, or STDIN_NAME
     pub name: String,
-    pub data: TableData,
+    /// Name of the sheet read from a workbook, or of the table read from an HTML or Markdown file
+    pub sheet: Option<String>,
+    /// Query that was run against a database
+    pub query: Option<String>,
+    pub dialect: Dialect,
+    /// Encoding the file was decoded with, kept so it can be reused when saving
+    pub encoding: DetectedEncoding,
+    /// Rows held in memory or read on demand from a memory-mapped file
+    pub data: Box<dyn Table>,
+    /// Lines skipped at the start of the file such as the metadata of an export, which
+    /// can be viewed apart from the table
+    pub preamble: Vec<String>,
+}
+
+impl OpenFileInfo {
+    /// The name of a file as it is opened, which for stdin is STDIN_NAME rather than "-"
+    pub fn name_of(filename: &str) -> String {
+        if table::is_stdin(filename) { STDIN_NAME.to_string() } else { filename.to_string() }
+    }
+
+    /// The data was read from stdin and so can not be read again
+    pub fn is_stdin(&self) -> bool {
+        self.name == STDIN_NAME
+    }
 }
 
 /// Stores the Applications state
@@ -37,10 +64,24 @@ impl AppState {
     }
 
     /// Get the stored recent files list
-    pub fn recent_files(&self) -> Vec<String> {
+    pub fn recent_files(&self) -> Vec<RecentFile> {
         Vec::clone(&self.settings.recent_files)
     }
 
+    /// Get the stored header override for a recent file
+    pub fn header_override(&self, filename: &str) -> Option<bool> {
+        self.settings.recent_files.iter()
+            .find(|e| e.path == filename)
+            .and_then(|e| e.header)
+    }
+
+    /// Get the stored query for a recent database file
+    pub fn recent_query(&self, filename: &str) -> Option<String> {
+        self.settings.recent_files.iter()
+            .find(|e| e.path == filename)
+            .and_then(|e| e.query.clone())
+    }
+
     /// Get the stored maximum number of recent files to keep
     pub fn max_recent_files(&self) -> usize {
         self.settings.max_recent_files
@@ -78,13 +119,29 @@ impl AppState {
 
     /// Add a file to the recent files list restricted by max_recent_files
     pub fn add_recent_file(&mut self, filename: &str) {
-        // If the file exists in the list
-        if self.settings.recent_files.iter().any(|e| e == filename) {
-            self.settings.recent_files.retain(|x| x != filename);
-        }
+        // If the file exists in the list keep its options
+        let entry = match self.settings.recent_files.iter().position(|e| e.path == filename) {
+            Some(i) => self.settings.recent_files.remove(i),
+            None => RecentFile::from(filename)
+        };
 
         if self.settings.recent_files.len() < self.settings.max_recent_files {
-            self.settings.recent_files.push(filename.to_string());
+            self.settings.recent_files.push(entry);
+        }
+    }
+
+    /// Set the header override for a recent file
+    /// None restores automatic header detection
+    pub fn set_header_override(&mut self, filename: &str, header: Option<bool>) {
+        if let Some(e) = self.settings.recent_files.iter_mut().find(|e| e.path == filename) {
+            e.header = header;
+        }
+    }
+
+    /// Set the query for a recent database file
+    pub fn set_recent_query(&mut self, filename: &str, query: Option<String>) {
+        if let Some(e) = self.settings.recent_files.iter_mut().find(|e| e.path == filename) {
+            e.query = query;
         }
     }
 
@@ -99,8 +156,20 @@ impl AppState {
         let mut ofi_ = Some(ofi);
         std::mem::swap(&mut self.ofi, &mut ofi_);
         let filename = String::from(&self.ofi.as_ref().unwrap().name);
-        // Add this file to the recent files list
+        let query = self.ofi.as_ref().unwrap().query.clone();
+
+        // Stdin is not a file that can be opened again
+        if self.ofi.as_ref().unwrap().is_stdin() {
+            return ofi_;
+        }
+
+        // Add this file to the recent files list along with the query a database was opened with
         self.add_recent_file(&filename);
+
+        if query.is_some() {
+            self.set_recent_query(&filename, query);
+        }
+
         ofi_
     }
 
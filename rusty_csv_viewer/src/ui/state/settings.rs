@@ -7,6 +7,7 @@ use std::path::Path;
 use serde::{Deserialize, Serialize};
 
 use crate::BoxedResult;
+use crate::table::split_member_path;
 use crate::utils::Point;
 
 /// Default values for the AppSetting structure
@@ -26,12 +27,55 @@ pub struct Settings {
     pub window_pos: Point<i32>,
     /// Window dimensions
     pub window_size: Point<u32>,
-    /// Recently opened file paths
-    pub recent_files: Vec<String>,
+    /// Recently opened files and the options they were opened with
+    pub recent_files: Vec<RecentFile>,
     /// Maximum number of recent files to store
     pub max_recent_files: usize,
 }
 
+/// A recently opened file and the per-file options it was opened with
+#[derive(Debug,Clone,Deserialize,Serialize,PartialEq,Default)]
+#[serde(from = "RecentFileEntry")]
+pub struct RecentFile {
+    /// Path to the file
+    pub path: String,
+    /// Overrides header detection when set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<bool>,
+    /// Query that was run against a database, run again when it is reopened
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+}
+
+impl From<&str> for RecentFile {
+    fn from(path: &str) -> Self {
+        RecentFile { path: path.to_string(), header: None, query: None }
+    }
+}
+
+/// Recent file entries are either a plain path as stored by older versions or a full entry
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RecentFileEntry {
+    Path(String),
+    Entry {
+        path: String,
+        #[serde(default)]
+        header: Option<bool>,
+        #[serde(default)]
+        query: Option<String>,
+    },
+}
+
+impl From<RecentFileEntry> for RecentFile {
+    fn from(entry: RecentFileEntry) -> Self {
+        match entry {
+            RecentFileEntry::Path(path) => RecentFile { path, header: None, query: None },
+            RecentFileEntry::Entry { path, header, query } => RecentFile { path, header, query },
+        }
+    }
+}
+
 /// Implementation for AppSettings class
 impl Settings {
     /// Construct AppSettings with default values
@@ -83,8 +127,9 @@ impl Settings {
     }
 
     /// Verify that the files in the recent files list are still valid files
-    fn verify_recent_files(files: &mut Vec<String>) {
-        *files = files.iter_mut().filter(|x| Path::new(x).is_file()).map(|x| x.to_string()).collect()
+    /// Members of an archive are kept as long as the archive exists
+    fn verify_recent_files(files: &mut Vec<RecentFile>) {
+        files.retain(|x| Path::new(split_member_path(&x.path).0).is_file())
     }
 }
 
@@ -94,11 +139,11 @@ mod tests {
 
     use super::*;
 
-    macro_rules! make_strvec {
+    macro_rules! make_recentvec {
         [ $($a:expr),+ ]
             =>
         {
-            vec![ $($a.to_owned()),+ ]
+            vec![ $(RecentFile::from($a)),+ ]
         }
     }
 
@@ -107,7 +152,7 @@ mod tests {
         let settings = Settings {
             window_pos: Point { x: 400, y: 500 },
             window_size: Point { x: 1000, y: 500 },
-            recent_files: make_strvec![
+            recent_files: make_recentvec![
                 "C:\\Temp\\data.csv",
                 "C:\\Users\\user\\Documents\\grades.csv"
             ],
@@ -140,7 +185,7 @@ mod tests {
         let expected = Settings {
             window_pos: Point { x: 400, y: 500 },
             window_size: Point { x: 300, y: 1000 },
-            recent_files: make_strvec![
+            recent_files: make_recentvec![
                 "C:\\temp\\new_data.csv",
                 "X:\\bigdata.csv"
             ],
@@ -172,7 +217,7 @@ mod tests {
         let s = Settings {
             window_pos: Point { x: 0, y: 2000 },
             window_size: Point { x: 1000, y: 1000 },
-            recent_files: make_strvec![ "X:\\secrets.csv" ],
+            recent_files: make_recentvec![ "X:\\secrets.csv" ],
             max_recent_files: 10,
         };
 
@@ -200,7 +245,7 @@ mod tests {
         r.window_size.x = 100;
         r.window_size.y = 150;
         r.recent_files.clear();
-        r.recent_files.push(String::from("G:\\Path\\To\\Hidden\\Treasure.csv"));
+        r.recent_files.push(RecentFile::from("G:\\Path\\To\\Hidden\\Treasure.csv"));
 
         r.save().expect("saving failed");
 
@@ -211,7 +256,7 @@ mod tests {
         let expected = Settings {
             window_pos: Point { x: 1234, y: 2200 },
             window_size: Point { x: 100, y: 150 },
-            recent_files: make_strvec![ "G:\\Path\\To\\Hidden\\Treasure.csv" ],
+            recent_files: make_recentvec![ "G:\\Path\\To\\Hidden\\Treasure.csv" ],
             max_recent_files: 10,
         };
 
@@ -219,4 +264,55 @@ mod tests {
 
         teardown_remove_settings_file();
     }
+
+    #[test]
+    fn test_serialize_recent_file_header_override() {
+        let settings = Settings {
+            recent_files: vec![
+                RecentFile { path: "C:\\Temp\\data.csv".to_string(), header: Some(true), query: None },
+                RecentFile::from("C:\\Temp\\other.csv"),
+            ],
+            .. Settings::new()
+        };
+
+        let r = serde_json::to_string(&settings).expect("serialization error");
+        let expected_settings: Settings = serde_json::from_str(&r).expect("deserialization error");
+
+        assert!(r.contains(r#"{"path":"C:\\Temp\\other.csv"}"#));
+        assert_eq!(&settings, &expected_settings);
+    }
+
+    #[test]
+    fn test_verify_recent_files_keeps_archive_members() {
+        let mut files = make_recentvec![ "Cargo.toml!/data/a.csv", "missing.zip!/a.csv", "Cargo.toml" ];
+        Settings::verify_recent_files(&mut files);
+
+        assert_eq!(files, make_recentvec![ "Cargo.toml!/data/a.csv", "Cargo.toml" ]);
+    }
+
+    #[test]
+    fn test_deserialize_recent_file_entries() {
+        let s =
+            r#"[ "X:\\bigdata.csv", { "path": "X:\\small.csv", "header": false } ]"#;
+
+        let r: Vec<RecentFile> = serde_json::from_str(s)
+            .expect("deserialization error during recent files read");
+
+        assert_eq!(r, vec![
+            RecentFile::from("X:\\bigdata.csv"),
+            RecentFile { path: "X:\\small.csv".to_string(), header: Some(false), query: None }
+        ]);
+    }
+
+    #[test]
+    fn test_recent_file_query() {
+        let s = r#"[ { "path": "X:\\sales.db", "query": "SELECT * FROM \"orders\"" } ]"#;
+
+        let r: Vec<RecentFile> = serde_json::from_str(s)
+            .expect("deserialization error during recent files read");
+
+        assert_eq!(r[0].query.as_deref(), Some("SELECT * FROM \"orders\""));
+        assert_eq!(serde_json::to_string(&r).expect("serialization error"),
+                   r#"[{"path":"X:\\sales.db","query":"SELECT * FROM \"orders\""}]"#);
+    }
 }
\ No newline at end of file